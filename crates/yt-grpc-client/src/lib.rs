@@ -8,25 +8,146 @@ pub mod youtube {
 
 pub use youtube::api::v3::*;
 
+mod net;
+
+pub use net::{NetworkOptions, resolve};
+
 use tonic::metadata::AsciiMetadataValue;
-use tonic::transport::Channel;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint, Identity};
 
 pub struct YouTubeClient {
     client: v3_data_live_chat_message_service_client::V3DataLiveChatMessageServiceClient<Channel>,
     api_key: Option<String>,
+    access_token: Option<String>,
+}
+
+/// Builder for a [`YouTubeClient`] with optional connection settings
+pub struct YouTubeClientBuilder {
+    addr: String,
+    api_key: Option<String>,
+    access_token: Option<String>,
+    identity: Option<Identity>,
+    max_decoding_message_size: Option<usize>,
+    network: NetworkOptions,
+}
+
+impl YouTubeClientBuilder {
+    /// API key sent as `x-goog-api-key` metadata with each request
+    pub fn api_key(mut self, api_key: Option<String>) -> Self {
+        self.api_key = api_key;
+        self
+    }
+
+    /// OAuth access token sent as `authorization: Bearer` metadata with each request. Pass a
+    /// currently valid token on every connect; the client doesn't refresh it.
+    pub fn access_token(mut self, access_token: Option<String>) -> Self {
+        self.access_token = access_token;
+        self
+    }
+
+    /// Client certificate and key presented to servers that require mutual TLS. Only used with
+    /// `https://` addresses.
+    pub fn client_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Largest response the client will decode, in bytes (tonic's default is 4MB)
+    pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+        self.max_decoding_message_size = Some(limit);
+        self
+    }
+
+    /// Name resolution and connection settings for TCP connections
+    pub fn network(mut self, options: NetworkOptions) -> Self {
+        self.network = options;
+        self
+    }
+
+    /// Connect to the server. `https://` addresses use TLS verified against the system trust
+    /// store; an address of the form `unix:///path/to.sock` connects over a Unix domain socket
+    /// without TLS.
+    pub async fn connect(self) -> Result<YouTubeClient, Box<dyn std::error::Error>> {
+        let channel = match self.addr.strip_prefix("unix://") {
+            Some(path) => connect_unix(path.to_string()).await?,
+            None => {
+                let mut endpoint = Endpoint::from_shared(self.addr)?;
+                if endpoint.uri().scheme_str() == Some("https") {
+                    let mut tls = ClientTlsConfig::new().with_enabled_roots();
+                    if let Some(identity) = self.identity {
+                        tls = tls.identity(identity);
+                    }
+                    endpoint = endpoint.tls_config(tls)?;
+                }
+                if let Some(limit) = self.network.connect_timeout {
+                    endpoint = endpoint.connect_timeout(limit);
+                }
+                if self.network.custom_resolver() {
+                    let options = self.network;
+                    // Only replaces the TCP dial; tonic still layers the TLS config above on top
+                    endpoint
+                        .connect_with_connector(tower::service_fn(move |uri| async move {
+                            let stream = net::connect_tcp(uri, options).await?;
+                            Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(stream))
+                        }))
+                        .await?
+                } else {
+                    endpoint.connect().await?
+                }
+            }
+        };
+        let mut client =
+            v3_data_live_chat_message_service_client::V3DataLiveChatMessageServiceClient::new(
+                channel,
+            );
+        if let Some(limit) = self.max_decoding_message_size {
+            client = client.max_decoding_message_size(limit);
+        }
+        Ok(YouTubeClient {
+            client,
+            api_key: self.api_key,
+            access_token: self.access_token,
+        })
+    }
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: String) -> Result<Channel, Box<dyn std::error::Error>> {
+    // tonic requires a URI, but the connector ignores it and always dials the socket
+    let channel = Endpoint::from_static("http://localhost")
+        .connect_with_connector(tower::service_fn(move |_: tonic::transport::Uri| {
+            let path = path.clone();
+            async move {
+                let stream = tokio::net::UnixStream::connect(path).await?;
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(stream))
+            }
+        }))
+        .await?;
+    Ok(channel)
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: String) -> Result<Channel, Box<dyn std::error::Error>> {
+    Err("Unix domain sockets are not supported on this platform".into())
 }
 
 impl YouTubeClient {
+    pub fn builder(addr: String) -> YouTubeClientBuilder {
+        YouTubeClientBuilder {
+            addr,
+            api_key: None,
+            access_token: None,
+            identity: None,
+            max_decoding_message_size: None,
+            network: NetworkOptions::default(),
+        }
+    }
+
     pub async fn connect(
         addr: String,
         api_key: Option<String>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let client =
-            v3_data_live_chat_message_service_client::V3DataLiveChatMessageServiceClient::connect(
-                addr,
-            )
-            .await?;
-        Ok(YouTubeClient { client, api_key })
+        Self::builder(addr).api_key(api_key).connect().await
     }
 
     pub async fn stream_comments(
@@ -50,6 +171,12 @@ impl YouTubeClient {
                 .metadata_mut()
                 .insert("x-goog-api-key", metadata_value);
         }
+        if let Some(access_token) = &self.access_token {
+            let metadata_value = AsciiMetadataValue::try_from(format!("Bearer {}", access_token))?;
+            request
+                .metadata_mut()
+                .insert("authorization", metadata_value);
+        }
 
         let response = self.client.stream_list(request).await?;
         Ok(response.into_inner())
@@ -0,0 +1,86 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// How host names are resolved and connected to, for networks where the defaults stall
+/// (e.g. a broken IPv6 route that AAAA answers still point at)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetworkOptions {
+    /// Try IPv4 addresses before IPv6 ones
+    pub prefer_ipv4: bool,
+    /// Give up on name resolution after this long
+    pub dns_timeout: Option<Duration>,
+    /// Give up on establishing a connection after this long
+    pub connect_timeout: Option<Duration>,
+}
+
+impl NetworkOptions {
+    /// Whether resolution differs from the system default, needing a custom resolver
+    pub fn custom_resolver(&self) -> bool {
+        self.prefer_ipv4 || self.dns_timeout.is_some()
+    }
+}
+
+/// Resolve a host name to the addresses to try, in order
+pub async fn resolve(
+    host: &str,
+    port: u16,
+    options: &NetworkOptions,
+) -> io::Result<Vec<SocketAddr>> {
+    let lookup = tokio::net::lookup_host((host, port));
+    let addrs = match options.dns_timeout {
+        Some(limit) => tokio::time::timeout(limit, lookup).await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "DNS resolution of '{}' timed out after {} seconds",
+                    host,
+                    limit.as_secs()
+                ),
+            )
+        })??,
+        None => lookup.await?,
+    };
+    let mut addrs: Vec<SocketAddr> = addrs.collect();
+    if options.prefer_ipv4 {
+        // Stable, so the resolver's order is kept within each family
+        addrs.sort_by_key(|addr| addr.is_ipv6());
+    }
+    Ok(addrs)
+}
+
+/// Connect to the host of a URI, trying its addresses in the order [`resolve`] returns them
+pub(crate) async fn connect_tcp(
+    uri: tonic::transport::Uri,
+    options: NetworkOptions,
+) -> io::Result<tokio::net::TcpStream> {
+    let host = uri
+        .host()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "server address has no host"))?;
+    // IPv6 literals keep their brackets in URIs
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = uri
+        .port_u16()
+        .unwrap_or(if uri.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        });
+
+    let mut last_error = None;
+    for addr in resolve(host, port, &options).await? {
+        match tokio::net::TcpStream::connect(addr).await {
+            Ok(stream) => {
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("'{}' did not resolve to any address", host),
+        )
+    }))
+}
@@ -0,0 +1,83 @@
+use ring::hmac;
+use serde_json::{Map, Value};
+
+/// Hex digits of the keyed hash kept in anonymized IDs (64 bits)
+const ID_HEX_LEN: usize = 16;
+
+/// Hex digits of the keyed hash used in pseudonyms
+const PSEUDONYM_HEX_LEN: usize = 8;
+
+/// Replaces author identities with stable pseudonyms for archives that will be shared.
+///
+/// Channel IDs become a salted HMAC-SHA256, and display names a pseudonym derived from it, so
+/// the same author maps to the same pseudonym throughout (and across runs with the same salt)
+/// without the original being recoverable.
+pub struct Anonymizer {
+    key: hmac::Key,
+}
+
+impl Anonymizer {
+    /// Use the given salt, or a random one when None (pseudonyms then differ between runs)
+    pub fn new(salt: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let key = match salt {
+            Some(salt) => hmac::Key::new(hmac::HMAC_SHA256, salt.as_bytes()),
+            None => hmac::Key::generate(hmac::HMAC_SHA256, &ring::rand::SystemRandom::new())
+                .map_err(|_| "Failed to generate a random anonymization salt")?,
+        };
+        Ok(Anonymizer { key })
+    }
+
+    /// Anonymize the author of the item and any user named in its details
+    pub fn apply(&self, item: &mut Value) {
+        if let Some(author) = object_mut(item, "author_details") {
+            self.anonymize_user(author);
+        }
+        if let Some(snippet) = object_mut(item, "snippet") {
+            if let Some(Value::String(id)) = snippet.get_mut("author_channel_id") {
+                *id = self.pseudonym(id).0;
+            }
+            if let Some(banned) = snippet
+                .get_mut("user_banned_details")
+                .and_then(|details| object_mut(details, "banned_user_details"))
+            {
+                self.anonymize_user(banned);
+            }
+        }
+    }
+
+    /// The anonymized form of a channel ID, as written to the output
+    pub fn channel_id(&self, channel_id: &str) -> String {
+        self.pseudonym(channel_id).0
+    }
+
+    /// Replace the channel ID and name of one user and drop the fields that link to the channel
+    fn anonymize_user(&self, user: &mut Map<String, Value>) {
+        let Some(channel_id) = user.get("channel_id").and_then(|id| id.as_str()) else {
+            // Without an ID there is nothing stable to derive a pseudonym from
+            user.remove("display_name");
+            return;
+        };
+        let (id, name) = self.pseudonym(channel_id);
+        user.insert("channel_id".to_string(), id.into());
+        user.insert("display_name".to_string(), name.into());
+        user.remove("channel_url");
+        user.remove("profile_image_url");
+    }
+
+    /// The anonymized channel ID and display name for a channel ID
+    fn pseudonym(&self, channel_id: &str) -> (String, String) {
+        let hash: String = hmac::sign(&self.key, channel_id.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        (
+            format!("anon-{}", &hash[..ID_HEX_LEN]),
+            format!("User {}", &hash[..PSEUDONYM_HEX_LEN]),
+        )
+    }
+}
+
+fn object_mut<'a>(value: &'a mut Value, name: &str) -> Option<&'a mut Map<String, Value>> {
+    value.get_mut(name).and_then(|value| value.as_object_mut())
+}
@@ -0,0 +1,90 @@
+use crate::message::{self, Item};
+use crate::output::{OutputFormat, RecordDelimiter};
+use std::io::{BufWriter, Write};
+
+const CSV_HEADER: &str = "published_at,author,author_channel_id,message,type,amount,currency";
+
+/// Convert an NDJSON archive to CSV with one row per chat item.
+///
+/// Header and control records are skipped, and malformed lines are counted rather than
+/// aborting the conversion.
+pub fn convert_to_csv(
+    input: &str,
+    output: Option<&str>,
+    format: OutputFormat,
+    delimiter: RecordDelimiter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let lines = crate::protobuf::json_lines(input, format, delimiter)?;
+
+    let writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(
+            std::fs::File::create(path)
+                .map_err(|e| format!("Failed to create output file '{}': {}", path, e))?,
+        ),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let mut writer = BufWriter::new(writer);
+
+    writeln!(writer, "{}", CSV_HEADER)?;
+
+    let mut rows = 0u64;
+    let mut malformed = 0u64;
+    for (index, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response: serde_json::Value = match serde_json::from_str(&line) {
+            Ok(value) => value,
+            Err(e) => {
+                log!("Skipping malformed line {}: {}", index + 1, e);
+                malformed += 1;
+                continue;
+            }
+        };
+
+        // Header and control records aren't chat responses
+        if response.get("event").is_some() {
+            continue;
+        }
+
+        for item in message::items(&response) {
+            let item = Item(item);
+            let message_type = item.message_type();
+            let amount = item.amount_micros().map(message::format_micros);
+            let row = [
+                item.published_at(),
+                item.author_name(),
+                item.author_channel_id(),
+                item.display_message(),
+                message_type.as_deref(),
+                amount.as_deref(),
+                item.currency(),
+            ];
+            let row: Vec<String> = row
+                .iter()
+                .map(|value| csv_escape(value.unwrap_or_default()))
+                .collect();
+            writeln!(writer, "{}", row.join(","))?;
+            rows += 1;
+        }
+    }
+
+    writer.flush()?;
+    log!(
+        "Converted {} items ({} malformed lines skipped)",
+        rows,
+        malformed
+    );
+    Ok(())
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
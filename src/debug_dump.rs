@@ -0,0 +1,74 @@
+use crate::redact;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Writes failed requests to timestamped files for attaching to bug reports.
+///
+/// Secrets are redacted from everything dumped, so the files are safe to share.
+#[derive(Clone, Debug)]
+pub struct DebugDump {
+    dir: PathBuf,
+}
+
+impl DebugDump {
+    /// Use `dir` for dumps, creating it if needed
+    pub fn new(dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create debug dump directory '{}': {}", dir, e))?;
+        Ok(DebugDump { dir: dir.into() })
+    }
+
+    /// Dump a non-success REST response
+    pub fn http_response(
+        &self,
+        context: &str,
+        url: &str,
+        status: reqwest::StatusCode,
+        headers: &reqwest::header::HeaderMap,
+        body: &str,
+    ) {
+        let mut dump = format!("url: {}\nstatus: {}\nheaders:\n", url, status);
+        for (name, value) in headers {
+            let _ = writeln!(dump, "  {}: {}", name, value.to_str().unwrap_or("<binary>"));
+        }
+        let _ = write!(dump, "\n{}\n", body);
+        self.write(context, &dump);
+    }
+
+    /// Dump a failed gRPC call or stream
+    pub fn grpc_status(&self, context: &str, status: &tonic::Status) {
+        let dump = format!(
+            "code: {:?}\nmessage: {}\nmetadata: {:?}\ndetails: {:?}\n",
+            status.code(),
+            status.message(),
+            status.metadata(),
+            status.details()
+        );
+        self.write(context, &dump);
+    }
+
+    /// Dump any other error (e.g. a gRPC connection failure)
+    pub fn error(&self, context: &str, error: &dyn std::error::Error) {
+        self.write(context, &format!("error: {}\ndebug: {:?}\n", error, error));
+    }
+
+    fn write(&self, context: &str, details: &str) {
+        let now = SystemTime::now();
+        let millis = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = self.dir.join(format!("{}-{}.txt", millis, context));
+        let contents = format!(
+            "time: {}\ncontext: {}\n{}",
+            humantime::format_rfc3339_millis(now),
+            context,
+            details
+        );
+        match std::fs::write(&path, redact::text(&contents)) {
+            Ok(()) => log!("Wrote debug dump: {}", path.display()),
+            Err(e) => log!("Failed to write debug dump '{}': {}", path.display(), e),
+        }
+    }
+}
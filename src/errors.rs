@@ -0,0 +1,94 @@
+//! Stable error codes for `--json-errors`.
+//!
+//! Supervisors switch on the `code` of a fatal error rather than matching its message. Typed
+//! errors are classified by their type; other errors can be given a code where they are
+//! raised by wrapping them in `CodedError`.
+
+use crate::rest::{ChatMessagesError, HttpError, VideoNotFound};
+use std::error::Error;
+
+/// An error tagged with the code reported by `--json-errors`
+pub struct CodedError {
+    code: &'static str,
+    source: Box<dyn Error>,
+}
+
+impl CodedError {
+    pub fn new(code: &'static str, source: impl Into<Box<dyn Error>>) -> Self {
+        CodedError {
+            code,
+            source: source.into(),
+        }
+    }
+}
+
+// Shown as the underlying error, so the default error output is unchanged
+impl std::fmt::Debug for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.source, f)
+    }
+}
+
+impl std::fmt::Display for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.source, f)
+    }
+}
+
+impl Error for CodedError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.source()
+    }
+}
+
+/// The stable code for an error, `error` when it can't be classified
+pub fn code(error: &(dyn Error + 'static)) -> &'static str {
+    if let Some(error) = error.downcast_ref::<CodedError>() {
+        return error.code;
+    }
+    // The API's own reason, present in the body of REST and gRPC errors alike
+    if error.to_string().contains("quotaExceeded") {
+        return "quota_exceeded";
+    }
+    if error.is::<VideoNotFound>() {
+        return "video_not_found";
+    }
+    if let Some(error) = error.downcast_ref::<ChatMessagesError>() {
+        return match error.status.as_u16() {
+            404 => "chat_not_found",
+            401 | 403 => "unauthorized",
+            _ => "http_error",
+        };
+    }
+    if let Some(error) = error.downcast_ref::<HttpError>() {
+        return match error.status.as_u16() {
+            401 | 403 => "unauthorized",
+            _ => "http_error",
+        };
+    }
+    if let Some(status) = error.downcast_ref::<tonic::Status>() {
+        return match status.code() {
+            tonic::Code::NotFound | tonic::Code::InvalidArgument => "chat_not_found",
+            tonic::Code::ResourceExhausted => "quota_exceeded",
+            tonic::Code::Unauthenticated | tonic::Code::PermissionDenied => "unauthorized",
+            tonic::Code::Unavailable | tonic::Code::DeadlineExceeded => "unavailable",
+            _ => "grpc_error",
+        };
+    }
+    if error.is::<tonic::transport::Error>() || error.is::<reqwest::Error>() {
+        return "connection_failed";
+    }
+    if error.is::<std::io::Error>() {
+        return "io_error";
+    }
+    "error"
+}
+
+/// The error as a one-line JSON object: `{"level":"error","code":"...","message":"..."}`
+pub fn to_json(error: &(dyn Error + 'static)) -> serde_json::Value {
+    serde_json::json!({
+        "level": "error",
+        "code": code(error),
+        "message": error.to_string(),
+    })
+}
@@ -0,0 +1,451 @@
+//! The `--filter` expression language.
+//!
+//! An expression combines comparisons with `&&`, `||`, `!` and parentheses, e.g.
+//! `type == "superChatEvent" && amount_micros > 5000000 || author.is_moderator`.
+//! Comparisons are `==`, `!=`, `<`, `<=`, `>`, `>=` and `contains` (substring), between
+//! fields, string literals, numbers and `true`/`false`. A field on its own is true when it is
+//! `true`, a non-zero number or a non-empty string. A comparison involving a field the item
+//! doesn't have is false.
+
+use crate::message::Item;
+
+/// Fields that can be used in expressions
+const FIELDS: &[&str] = &[
+    "id",
+    "type",
+    "message",
+    "published_at",
+    "amount_micros",
+    "currency",
+    "related_message_id",
+    "author.name",
+    "author.channel_id",
+    "author.role",
+    "author.is_owner",
+    "author.is_moderator",
+    "author.is_sponsor",
+    "author.is_verified",
+];
+
+/// A parsed filter expression
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CompareOp, Operand),
+    Truthy(Operand),
+}
+
+#[derive(Clone, Debug)]
+pub enum Operand {
+    Field(String),
+    Literal(Val),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+/// A value an operand evaluates to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Val {
+    Str(String),
+    Num(f64),
+    Bool(bool),
+}
+
+impl Expr {
+    /// Parse an expression, reporting the position of any syntax error
+    pub fn parse(input: &str) -> Result<Expr, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    /// Evaluate the expression against one chat item
+    pub fn matches(&self, item: Item) -> bool {
+        match self {
+            Expr::Or(a, b) => a.matches(item) || b.matches(item),
+            Expr::And(a, b) => a.matches(item) && b.matches(item),
+            Expr::Not(a) => !a.matches(item),
+            Expr::Truthy(operand) => match operand.eval(item) {
+                Some(Val::Bool(b)) => b,
+                Some(Val::Num(n)) => n != 0.0,
+                Some(Val::Str(s)) => !s.is_empty(),
+                None => false,
+            },
+            Expr::Compare(a, op, b) => match (a.eval(item), b.eval(item)) {
+                (Some(a), Some(b)) => compare(&a, *op, &b),
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Operand {
+    fn eval(&self, item: Item) -> Option<Val> {
+        let field = match self {
+            Operand::Literal(val) => return Some(val.clone()),
+            Operand::Field(field) => field.as_str(),
+        };
+        let str_val = |s: Option<&str>| s.map(|s| Val::Str(s.to_string()));
+        match field {
+            "id" => str_val(crate::message::str_field(item.0, "id")),
+            "type" => item.message_type().map(Val::Str),
+            "message" => str_val(item.display_message()),
+            "published_at" => str_val(item.published_at()),
+            "amount_micros" => item.amount_micros().map(|micros| Val::Num(micros as f64)),
+            "currency" => str_val(item.currency()),
+            "related_message_id" => str_val(item.related_message_id()),
+            "author.name" => str_val(item.author_name()),
+            "author.channel_id" => str_val(item.author_channel_id()),
+            "author.role" => Some(Val::Str(item.role().as_str().to_string())),
+            flag => {
+                let name = match flag {
+                    "author.is_owner" => "is_chat_owner",
+                    "author.is_moderator" => "is_chat_moderator",
+                    "author.is_sponsor" => "is_chat_sponsor",
+                    "author.is_verified" => "is_verified",
+                    _ => return None,
+                };
+                let value = item
+                    .author_details()
+                    .and_then(|author| crate::message::field(author, name))
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                Some(Val::Bool(value))
+            }
+        }
+    }
+}
+
+fn compare(a: &Val, op: CompareOp, b: &Val) -> bool {
+    use std::cmp::Ordering;
+
+    if op == CompareOp::Contains {
+        return match (a, b) {
+            (Val::Str(a), Val::Str(b)) => a.contains(b.as_str()),
+            _ => false,
+        };
+    }
+    let ordering = match (a, b) {
+        (Val::Str(a), Val::Str(b)) => Some(a.cmp(b)),
+        (Val::Num(a), Val::Num(b)) => a.partial_cmp(b),
+        (Val::Bool(a), Val::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    match (op, ordering) {
+        (CompareOp::Ne, None) => true,
+        (_, None) => false,
+        (CompareOp::Eq, Some(o)) => o == Ordering::Equal,
+        (CompareOp::Ne, Some(o)) => o != Ordering::Equal,
+        (CompareOp::Lt, Some(o)) => o == Ordering::Less,
+        (CompareOp::Le, Some(o)) => o != Ordering::Greater,
+        (CompareOp::Gt, Some(o)) => o == Ordering::Greater,
+        (CompareOp::Ge, Some(o)) => o != Ordering::Less,
+        (CompareOp::Contains, Some(_)) => unreachable!("handled above"),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Str(s) => write!(f, "string \"{}\"", s),
+            Token::Num(n) => write!(f, "number {}", n),
+            Token::Op(op) => write!(f, "operator {:?}", op),
+            Token::And => write!(f, "'&&'"),
+            Token::Or => write!(f, "'||'"),
+            Token::Not => write!(f, "'!'"),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let two: String = input[start..].chars().take(2).collect();
+        let token = match two.as_str() {
+            "&&" => Some(Token::And),
+            "||" => Some(Token::Or),
+            "==" => Some(Token::Op(CompareOp::Eq)),
+            "!=" => Some(Token::Op(CompareOp::Ne)),
+            "<=" => Some(Token::Op(CompareOp::Le)),
+            ">=" => Some(Token::Op(CompareOp::Ge)),
+            _ => None,
+        };
+        if let Some(token) = token {
+            chars.next();
+            chars.next();
+            tokens.push(token);
+            continue;
+        }
+
+        match c {
+            '!' | '(' | ')' | '<' | '>' => {
+                chars.next();
+                tokens.push(match c {
+                    '!' => Token::Not,
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '<' => Token::Op(CompareOp::Lt),
+                    _ => Token::Op(CompareOp::Gt),
+                });
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => s.push(escaped),
+                            None => return Err("unterminated string".to_string()),
+                        },
+                        Some((_, c)) => s.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.' || (i == start && c == '-')) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let text = &input[start..end];
+                let n = text
+                    .parse()
+                    .map_err(|_| format!("invalid number '{}' at position {}", text, start))?;
+                tokens.push(Token::Num(n));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let word = &input[start..end];
+                tokens.push(match word {
+                    "contains" => Token::Op(CompareOp::Contains),
+                    _ => Token::Ident(word.to_string()),
+                });
+            }
+            _ => return Err(format!("unexpected '{}' at position {}", c, start)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.next();
+            let expr = self.or()?;
+            return match self.next() {
+                Some(Token::RParen) => Ok(expr),
+                Some(token) => Err(format!("expected ')' but found {}", token)),
+                None => Err("expected ')' but the expression ended".to_string()),
+            };
+        }
+
+        let left = self.operand()?;
+        match self.peek() {
+            Some(&Token::Op(op)) => {
+                self.next();
+                Ok(Expr::Compare(left, op, self.operand()?))
+            }
+            _ => Ok(Expr::Truthy(left)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok(Operand::Literal(Val::Bool(true))),
+                "false" => Ok(Operand::Literal(Val::Bool(false))),
+                _ if FIELDS.contains(&name.as_str()) => Ok(Operand::Field(name)),
+                _ => Err(format!(
+                    "unknown field '{}' (available: {})",
+                    name,
+                    FIELDS.join(", ")
+                )),
+            },
+            Some(Token::Str(s)) => Ok(Operand::Literal(Val::Str(s))),
+            Some(Token::Num(n)) => Ok(Operand::Literal(Val::Num(n))),
+            Some(token) => Err(format!("expected a field or value but found {}", token)),
+            None => Err("expected a field or value but the expression ended".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn text_item(message: &str) -> Value {
+        json!({
+            "id": "m1",
+            "snippet": { "type": 1, "display_message": message },
+            "author_details": { "channel_id": "UC1", "is_chat_moderator": true },
+        })
+    }
+
+    fn superchat_item(amount_micros: u64) -> Value {
+        json!({
+            "id": "m2",
+            "snippet": {
+                "type": 15,
+                "super_chat_details": { "amount_micros": amount_micros, "currency": "USD" },
+            },
+            "author_details": { "channel_id": "UC2" },
+        })
+    }
+
+    fn matches(expression: &str, item: &Value) -> bool {
+        Expr::parse(expression).unwrap().matches(Item(item))
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let item = text_item("hi");
+        // Parsed as `false || (true && true)`, not `(false || true) && ...`
+        assert!(matches("false || true && author.is_moderator", &item));
+        assert!(!matches("true && false || false", &item));
+        assert!(matches("false && true || true", &item));
+    }
+
+    #[test]
+    fn not_applies_to_the_nearest_operand() {
+        let item = text_item("hi");
+        assert!(!matches("!author.is_moderator", &item));
+        assert!(matches("!author.is_owner && author.is_moderator", &item));
+        assert!(!matches("!true || false", &item));
+        assert!(matches("!!author.is_moderator", &item));
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        let item = text_item("hi");
+        assert!(!matches("(false || true) && author.is_owner", &item));
+        assert!(matches("!(author.is_owner || false)", &item));
+        assert!(Expr::parse("(true").is_err());
+    }
+
+    #[test]
+    fn string_escapes() {
+        let item = text_item(r#"say "hi" \o/"#);
+        assert!(matches(r#"message == "say \"hi\" \\o/""#, &item));
+        assert!(matches(r#"message contains "\"hi\"""#, &item));
+        assert!(Expr::parse(r#"message == "open"#).is_err());
+    }
+
+    #[test]
+    fn negative_and_decimal_numbers() {
+        let item = superchat_item(1_500_000);
+        assert!(matches("amount_micros > -1", &item));
+        assert!(matches("amount_micros > 1499999.5", &item));
+        assert!(matches("amount_micros <= 1500000.0", &item));
+        assert!(!matches("amount_micros < -0.5", &item));
+        assert!(Expr::parse("amount_micros > 1.2.3").is_err());
+    }
+
+    #[test]
+    fn unknown_fields_and_trailing_tokens_are_errors() {
+        let error = Expr::parse("author.nmae == \"x\"").unwrap_err();
+        assert!(error.contains("unknown field 'author.nmae'"), "{}", error);
+        let error = Expr::parse("author.is_owner author.is_moderator").unwrap_err();
+        assert!(error.contains("unexpected"), "{}", error);
+        let error = Expr::parse("amount_micros >").unwrap_err();
+        assert!(error.contains("ended"), "{}", error);
+    }
+
+    #[test]
+    fn comparisons_with_a_missing_field_are_false() {
+        let item = text_item("hi");
+        assert!(!matches("amount_micros > 0", &item));
+        assert!(!matches("amount_micros <= 0", &item));
+        assert!(!matches("currency == \"USD\"", &item));
+        assert!(!matches("currency != \"USD\"", &item));
+        assert!(!matches("currency", &item));
+        assert!(matches("!(currency == \"USD\")", &item));
+        assert!(matches("currency == \"USD\"", &superchat_item(1)));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::anonymize::Anonymizer;
+use crate::expr::Expr;
+use crate::message::{Item, Role};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::time::SystemTime;
+
+/// Whether items that refer to an earlier message are kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Threading {
+    /// Only items without a related message
+    TopLevel,
+    /// Only items with a related message
+    Replies,
+}
+
+/// Tracks which authors have been seen, to mark their first message
+#[derive(Default)]
+pub struct FirstSeen {
+    /// Channel IDs of every author seen so far
+    seen: HashSet<String>,
+    /// Drop items from authors who were already seen (`--new-chatters-only`)
+    pub new_only: bool,
+}
+
+impl FirstSeen {
+    pub fn new(new_only: bool) -> Self {
+        FirstSeen {
+            seen: HashSet::new(),
+            new_only,
+        }
+    }
+
+    /// Count the authors of a response as seen without annotating it (e.g. from an archive
+    /// being resumed)
+    pub fn seed(&mut self, response: &Value) {
+        for item in crate::message::items(response) {
+            if let Some(id) = Item(item).author_channel_id() {
+                self.seen.insert(id.to_string());
+            }
+        }
+    }
+
+    /// Number of distinct authors seen
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Set `author_details.first_seen` on an item with an author, remembering the author.
+    /// With an anonymizer, authors are tracked by their anonymized ID, the form a resumed
+    /// archive holds.
+    fn annotate(&mut self, item: &mut Value, anonymizer: Option<&Anonymizer>) {
+        let Some(id) = Item(item).author_channel_id() else {
+            return;
+        };
+        let id = match anonymizer {
+            Some(anonymizer) => anonymizer.channel_id(id),
+            None => id.to_string(),
+        };
+        let first_seen = self.seen.insert(id);
+        if let Some(author) = item
+            .get_mut("author_details")
+            .and_then(|author| author.as_object_mut())
+        {
+            author.insert("first_seen".to_string(), first_seen.into());
+        }
+    }
+}
+
+/// Per-item annotation and filtering applied to each response before it is written
+#[derive(Default)]
+pub struct ItemFilter {
+    /// Only keep items whose author has one of these roles (all items when empty)
+    pub only_roles: Vec<Role>,
+    /// Only keep items matching this `--filter` expression
+    pub expression: Option<Expr>,
+    /// Only keep items whose text contains one of these words or matches `match_regex`
+    /// (lowercase; matched case-insensitively)
+    pub match_words: Vec<String>,
+    /// Only keep items whose text matches this or contains one of `match_words`
+    pub match_regex: Option<regex::Regex>,
+    /// Drop items whose text contains any of these words (lowercase; case-insensitive)
+    pub exclude_words: Vec<String>,
+    /// Drop items published before this (items without a parseable timestamp are kept)
+    pub since: Option<SystemTime>,
+    /// Keep only top-level items or only replies (all items when None)
+    pub threading: Option<Threading>,
+    /// Stable-sort the remaining items by `published_at` (items without a parseable timestamp
+    /// come first)
+    pub sort_by_published: bool,
+    /// Mark each author's first message of the session, optionally keeping only those
+    pub first_seen: Option<FirstSeen>,
+    /// Replace author identities with pseudonyms after filtering
+    pub anonymizer: Option<Anonymizer>,
+    /// Replace each item's `author_details` with top-level `author_*` fields (see
+    /// [`flatten_author`])
+    pub flatten_author: bool,
+}
+
+impl ItemFilter {
+    /// Add the normalized `author_details.role` to every item and `snippet.related_message_id`
+    /// to items that refer to an earlier message, drop items that don't match, and optionally
+    /// sort, anonymize and flatten the rest
+    pub fn apply(&mut self, response: &mut Value) {
+        let Some(items) = response
+            .get_mut("items")
+            .and_then(|items| items.as_array_mut())
+        else {
+            return;
+        };
+
+        // Every item counts towards the seen authors, including ones filtered out below
+        let new_only = match self.first_seen {
+            Some(ref mut first_seen) => {
+                let anonymizer = self.anonymizer.as_ref();
+                items
+                    .iter_mut()
+                    .for_each(|item| first_seen.annotate(item, anonymizer));
+                first_seen.new_only
+            }
+            None => false,
+        };
+
+        items.retain_mut(|item| {
+            let role = Item(item).role();
+            if let Some(author) = item
+                .get_mut("author_details")
+                .and_then(|author| author.as_object_mut())
+            {
+                author.insert("role".to_string(), role.as_str().into());
+            }
+            let related = Item(item).related_message_id().map(|id| id.to_string());
+            if let Some(ref related) = related
+                && let Some(snippet) = item
+                    .get_mut("snippet")
+                    .and_then(|snippet| snippet.as_object_mut())
+            {
+                snippet.insert("related_message_id".to_string(), related.as_str().into());
+            }
+            let threading_matches = match self.threading {
+                None => true,
+                Some(Threading::TopLevel) => related.is_none(),
+                Some(Threading::Replies) => related.is_some(),
+            };
+            threading_matches
+                && (!new_only || is_first_seen(item))
+                && self.published_in_range(Item(item))
+                && (self.only_roles.is_empty() || self.only_roles.contains(&role))
+                && self.text_matches(Item(item))
+                && self
+                    .expression
+                    .as_ref()
+                    .is_none_or(|expression| expression.matches(Item(item)))
+        });
+
+        if self.sort_by_published {
+            items.sort_by_cached_key(|item| {
+                Item(item)
+                    .published_at()
+                    .and_then(|at| humantime::parse_rfc3339_weak(at).ok())
+            });
+        }
+
+        if let Some(ref anonymizer) = self.anonymizer {
+            items.iter_mut().for_each(|item| anonymizer.apply(item));
+        }
+
+        if self.flatten_author {
+            items.iter_mut().for_each(flatten_author);
+        }
+    }
+
+    /// Whether the item was published at or after `since`
+    fn published_in_range(&self, item: Item) -> bool {
+        let Some(since) = self.since else {
+            return true;
+        };
+        item.published_at()
+            .and_then(|at| humantime::parse_rfc3339_weak(at).ok())
+            .is_none_or(|at| at >= since)
+    }
+
+    /// Apply the word and regex filters to the item's display message
+    fn text_matches(&self, item: Item) -> bool {
+        if self.match_words.is_empty()
+            && self.match_regex.is_none()
+            && self.exclude_words.is_empty()
+        {
+            return true;
+        }
+
+        let text = item.display_message().unwrap_or_default();
+        let lowercase = text.to_lowercase();
+        if self
+            .exclude_words
+            .iter()
+            .any(|word| lowercase.contains(word.as_str()))
+        {
+            return false;
+        }
+        if self.match_words.is_empty() && self.match_regex.is_none() {
+            return true;
+        }
+        self.match_words
+            .iter()
+            .any(|word| lowercase.contains(word.as_str()))
+            || self
+                .match_regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(text))
+    }
+}
+
+/// Move the fields of the item's `author_details` to the top level, prefixed with `author_`
+/// (`display_name` becomes `author_name`), and copy `snippet.display_message` to `message`,
+/// so an item loads as one flat row. The response around the items is left as it is, so
+/// `--resume` still works.
+fn flatten_author(item: &mut Value) {
+    let Some(object) = item.as_object_mut() else {
+        return;
+    };
+    if let Some(Value::Object(author)) = object.remove("author_details") {
+        for (name, value) in author {
+            let name = match name.as_str() {
+                "display_name" => "author_name".to_string(),
+                _ => format!("author_{}", name),
+            };
+            object.insert(name, value);
+        }
+    }
+    let message = object
+        .get("snippet")
+        .and_then(|snippet| snippet.get("display_message"))
+        .cloned();
+    if let Some(message) = message {
+        object.insert("message".to_string(), message);
+    }
+}
+
+/// Whether the item was marked as its author's first message
+fn is_first_seen(item: &Value) -> bool {
+    item.get("author_details")
+        .and_then(|author| author.get("first_seen"))
+        .and_then(|first_seen| first_seen.as_bool())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response(authors: &[&str]) -> Value {
+        let items: Vec<Value> = authors
+            .iter()
+            .map(|author| json!({"id": author, "author_details": {"channel_id": author}}))
+            .collect();
+        json!({ "items": items })
+    }
+
+    fn filter(first_seen: FirstSeen) -> ItemFilter {
+        ItemFilter {
+            first_seen: Some(first_seen),
+            anonymizer: Some(Anonymizer::new(Some("salt")).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resume_from_anonymized_archive_remembers_authors() {
+        let mut archived = response(&["UCalice"]);
+        filter(FirstSeen::new(false)).apply(&mut archived);
+
+        let mut first_seen = FirstSeen::new(true);
+        first_seen.seed(&archived);
+        let mut resumed = response(&["UCalice", "UCbob"]);
+        filter(first_seen).apply(&mut resumed);
+
+        let items = resumed["items"].as_array().unwrap();
+        assert_eq!(items.len(), 1, "{}", resumed);
+        assert_eq!(items[0]["id"], "UCbob");
+        assert_eq!(items[0]["author_details"]["first_seen"], true);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::message::{self, Item};
+use crate::sink::MessageSink;
+use rdkafka::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::JoinSet;
+
+/// Number of items queued for the producer before new ones are dropped, by default
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+/// Maximum number of records awaiting a delivery report at once
+const MAX_IN_FLIGHT: usize = 1000;
+
+/// How long librdkafka keeps retrying a record before reporting it as failed
+const MESSAGE_TIMEOUT_MS: &str = "30000";
+
+/// Kafka settings taken from the command line
+pub struct KafkaConfig {
+    /// Comma-separated bootstrap servers
+    pub brokers: String,
+    pub topic: String,
+    /// Number of items queued for the producer before new ones are dropped
+    pub queue_size: usize,
+}
+
+/// Produces each chat item as a JSON record, keyed by the author's channel ID so one
+/// author's messages stay in one partition.
+///
+/// Items are queued and produced by a background task, so Kafka backpressure never stalls the
+/// stream. When the queue is full, new items are dropped and counted.
+pub struct KafkaSink {
+    tx: mpsc::Sender<Value>,
+    task: tokio::task::JoinHandle<()>,
+    dropped: u64,
+}
+
+impl KafkaSink {
+    pub fn start(config: KafkaConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", &config.brokers)
+            .set("message.timeout.ms", MESSAGE_TIMEOUT_MS)
+            .create()
+            .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
+        log!(
+            "Producing messages to Kafka topic '{}' at {}",
+            config.topic,
+            config.brokers
+        );
+
+        let (tx, rx) = mpsc::channel(config.queue_size.max(1));
+        let task = tokio::spawn(run(producer, config.topic, rx));
+        Ok(KafkaSink {
+            tx,
+            task,
+            dropped: 0,
+        })
+    }
+}
+
+impl MessageSink for KafkaSink {
+    /// Queue each item of the response to be produced
+    fn offer(&mut self, response: &Value) {
+        for item in message::items(response) {
+            if self.tx.try_send(item.clone()).is_err() {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Finish producing queued items, giving up once the grace period has elapsed
+    fn shutdown(self: Box<Self>, grace: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let KafkaSink { tx, task, dropped } = *self;
+        Box::pin(async move {
+            drop(tx);
+            if dropped > 0 {
+                log!("Kafka: {} items dropped (queue full)", dropped);
+            }
+            if tokio::time::timeout(grace, task).await.is_err() {
+                log!(
+                    "Kafka deliveries did not finish within {} seconds, giving up",
+                    grace.as_secs()
+                );
+            }
+        })
+    }
+}
+
+async fn run(producer: FutureProducer, topic: String, mut rx: mpsc::Receiver<Value>) {
+    let topic = Arc::new(topic);
+    let permits = Arc::new(Semaphore::new(MAX_IN_FLIGHT));
+    let mut deliveries = JoinSet::new();
+    let mut produced = 0u64;
+    let mut failed = 0u64;
+    let mut count = |ok: bool| {
+        if ok {
+            produced += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    while let Some(item) = rx.recv().await {
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        let producer = producer.clone();
+        let topic = topic.clone();
+        deliveries.spawn(async move {
+            let _permit = permit;
+            produce(&producer, &topic, &item).await
+        });
+
+        while let Some(result) = deliveries.try_join_next() {
+            count(result.unwrap_or(false));
+        }
+    }
+
+    while let Some(result) = deliveries.join_next().await {
+        count(result.unwrap_or(false));
+    }
+    log!("Kafka: {} produced, {} failed", produced, failed);
+}
+
+/// Produce one item and wait for its delivery report. Returns whether it was delivered.
+async fn produce(producer: &FutureProducer, topic: &str, item: &Value) -> bool {
+    let payload = item.to_string();
+    let key = Item(item).author_channel_id().unwrap_or_default();
+    let record = FutureRecord::to(topic).key(key).payload(&payload);
+    match producer.send(record, Timeout::Never).await {
+        Ok(_) => true,
+        Err((e, _)) => {
+            log!("Kafka produce failed: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::quota::ApiCalls;
+use crate::rest;
+
+/// Settings for `list-live`, taken from the command line
+pub struct ListLiveConfig<'a> {
+    pub rest_api_address: &'a str,
+    pub channel_id: &'a str,
+    pub api_key: Option<&'a str>,
+    pub access_token: Option<&'a str>,
+    /// Print one JSON object per broadcast instead of a table
+    pub json: bool,
+}
+
+/// A live or upcoming broadcast of the channel
+struct Broadcast {
+    status: &'static str,
+    video: rest::LiveVideo,
+    /// Actual start time when live, scheduled start time when upcoming
+    start_time: Option<String>,
+}
+
+/// Print the channel's live and upcoming broadcasts to stdout.
+///
+/// Each status is a separate search.list call (100 quota units each), followed by one
+/// videos.list call for the start times.
+pub async fn list(
+    client: &reqwest::Client,
+    config: ListLiveConfig<'_>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let api_calls = ApiCalls::default();
+    let mut broadcasts = Vec::new();
+    for status in ["live", "upcoming"] {
+        let videos = rest::search_videos(
+            client,
+            config.rest_api_address,
+            config.channel_id,
+            status,
+            config.api_key,
+            config.access_token,
+            None,
+            &api_calls,
+        )
+        .await?;
+        broadcasts.extend(videos.into_iter().map(|video| Broadcast {
+            status,
+            video,
+            start_time: None,
+        }));
+    }
+
+    let video_ids: Vec<&str> = broadcasts
+        .iter()
+        .map(|broadcast| broadcast.video.video_id.as_str())
+        .collect();
+    let mut start_times = rest::fetch_start_times(
+        client,
+        config.rest_api_address,
+        &video_ids,
+        config.api_key,
+        config.access_token,
+        None,
+        &api_calls,
+    )
+    .await?;
+    for broadcast in &mut broadcasts {
+        broadcast.start_time = start_times.remove(&broadcast.video.video_id);
+    }
+
+    if broadcasts.is_empty() {
+        log!(
+            "Channel {} has no live or upcoming broadcasts",
+            config.channel_id
+        );
+    } else if config.json {
+        for broadcast in &broadcasts {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "video_id": broadcast.video.video_id,
+                    "status": broadcast.status,
+                    "start_time": broadcast.start_time,
+                    "title": broadcast.video.title,
+                })
+            );
+        }
+    } else {
+        println!(
+            "{:<8}  {:<11}  {:<20}  TITLE",
+            "STATUS", "VIDEO ID", "START"
+        );
+        for broadcast in &broadcasts {
+            println!(
+                "{:<8}  {:<11}  {:<20}  {}",
+                broadcast.status,
+                broadcast.video.video_id,
+                broadcast.start_time.as_deref().unwrap_or("-"),
+                broadcast.video.title.as_deref().unwrap_or("untitled")
+            );
+        }
+    }
+
+    log!("API usage: {}", api_calls.summary());
+    Ok(())
+}
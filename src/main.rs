@@ -1,228 +1,1361 @@
-use clap::Parser;
-use std::fs::OpenOptions;
-use std::io::Write;
-use tokio_stream::StreamExt;
-use yt_grpc_client::YouTubeClient;
+use clap::{Parser, Subcommand};
+use filter::ItemFilter;
+use message::Role;
+use output::{
+    DelimiterAsNewline, FlushPolicy, Output, OutputConfig, OutputFormat, OverflowPolicy,
+    RecordDelimiter,
+};
+use post::{PostConfig, PostSink};
+use sink::MessageSink;
+
+// First, so the `log!` macro is in scope in every other module
+#[macro_use]
+mod redact;
+
+mod anonymize;
+mod convert;
+mod debug_dump;
+mod errors;
+mod expr;
+mod filter;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod list_live;
+mod member;
+mod message;
+mod output;
+mod post;
+mod print_config;
+mod protobuf;
+mod quota;
+mod reconnect;
+mod replay;
+mod rest;
+mod selftest;
+mod sequence;
+mod signals;
+mod sink;
+mod split;
+mod stats;
+mod status;
+mod stream;
+mod superchat;
+mod tail;
+
+use signals::Signals;
+use stats::Stats;
+use status::StatusFile;
+use stream::{StreamConfig, Transport};
+
+/// Version of the output record layout, written in the --write-header record
+const SCHEMA_VERSION: &str = "1";
+
+/// Default --max-message-size: 16MB, four times tonic's default
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Default --user-agent
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Default --rest-timeout-secs
+const DEFAULT_REST_TIMEOUT_SECS: u64 = 30;
+
+/// Version string including the commit and date the binary was built from
+const VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("GIT_HASH"),
+    " ",
+    env!("BUILD_DATE"),
+    ")"
+);
 
 /// YouTube Live Comment Fetcher - Streams live chat messages from YouTube videos
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version = VERSION, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// YouTube video ID to fetch comments from (optional when --resume is used)
     #[arg(long)]
     video_id: Option<String>,
 
-    /// Path to file containing the API key for authentication
+    /// Find the video to capture by searching this channel for a live broadcast instead of
+    /// passing --video-id (search costs 100 quota units)
+    #[arg(long, conflicts_with = "video_id")]
+    channel_id: Option<String>,
+
+    /// Which broadcast to use when --channel-id finds more than one live video [default: only]
+    #[arg(long, value_enum, requires = "channel_id")]
+    pick: Option<rest::Pick>,
+
+    /// Path to file containing the API key for authentication ("-" reads the key from stdin)
     #[arg(long)]
     api_key_path: Option<String>,
 
-    /// Wait time in seconds before reconnecting after connection failure (default: 5)
+    /// Path to a file containing an OAuth access token, sent with videos.list (so the owner's
+    /// private and unlisted broadcasts can be found) and as gRPC `authorization` metadata.
+    /// Re-read on each lookup and (re)connect, so an external helper can keep it fresh.
+    #[arg(long)]
+    access_token_path: Option<String>,
+
+    /// Wait time in seconds before reconnecting after connection failure (default: 5; 0
+    /// reconnects immediately)
     #[arg(long, default_value = "5")]
     reconnect_wait_secs: u64,
 
+    /// How the wait between reconnect attempts grows
+    #[arg(long, value_enum, default_value_t = reconnect::PolicyKind::Fixed)]
+    reconnect_policy: reconnect::PolicyKind,
+
+    /// Longest wait between reconnect attempts with --reconnect-policy exponential-capped
+    #[arg(long, default_value = "300")]
+    reconnect_max_wait_secs: u64,
+
+    /// Seconds to wait before reconnecting when the stream has ended repeatedly in quick
+    /// succession (e.g. a chat that closes as soon as it is opened)
+    #[arg(long, default_value = "60")]
+    stream_end_cooldown_secs: u64,
+
     /// Path to output file where JSON messages will be written (one per line)
     #[arg(long)]
     output_file: Option<String>,
 
+    /// Also write everything written to --output-file to stdout, to archive and watch at once
+    #[arg(long, requires = "output_file")]
+    tee: bool,
+
+    /// Stop cleanly (as on SIGTERM) once the output file has reached this many bytes, counting
+    /// what it held before the run
+    #[arg(long, requires = "output_file")]
+    max_output_bytes: Option<u64>,
+
     /// Resume streaming from the last message in the output file
     #[arg(long)]
     resume: bool,
+
+    /// Stream fresh and drop messages published before this RFC 3339 time. With --resume,
+    /// only used if the saved page token is rejected (the last saved message's time is
+    /// preferred)
+    #[arg(long, value_parser = humantime::parse_rfc3339_weak)]
+    resume_from: Option<std::time::SystemTime>,
+
+    /// Transport used to receive messages: gRPC streaming, or polling the REST API
+    #[arg(long, value_enum, default_value_t = Transport::Grpc)]
+    transport: Transport,
+
+    /// Write control records (e.g. {"event":"reconnect",...}) into the output alongside messages
+    #[arg(long)]
+    emit_control_events: bool,
+
+    /// Retry the initial chat ID lookup and connection this many times, with backoff, before
+    /// exiting (for runs launched before the network is up)
+    #[arg(long, default_value = "0")]
+    startup_retries: u32,
+
+    /// Seconds to wait for a connection (and its first response) before giving up; a failed
+    /// initial connection exits (unless --startup-retries is set), a failed reconnect is retried
+    /// after the reconnect wait
+    #[arg(long, default_value = "30")]
+    connect_timeout_secs: u64,
+
+    /// Seconds to wait on shutdown for pending output to be written and flushed (default: 5)
+    #[arg(long, default_value = "5")]
+    shutdown_grace_secs: u64,
+
+    /// Number of records buffered between the stream reader and the output writer (default: 1024)
+    #[arg(long, default_value = "1024")]
+    output_buffer_size: usize,
+
+    /// Total number of messages the in-memory queues (output buffer, webhook and Kafka queues)
+    /// may hold, split evenly between the ones in use; overrides --output-buffer-size
+    #[arg(long, conflicts_with = "output_buffer_size")]
+    max_memory_messages: Option<usize>,
+
+    /// What to do when the output buffer is full: drop records, or block reading until it drains
+    #[arg(long, value_enum, default_value_t = OverflowPolicy::Block)]
+    overflow: OverflowPolicy,
+
+    /// Flush output after this many records instead of after every record
+    #[arg(long)]
+    flush_every: Option<u64>,
+
+    /// Flush buffered output at least this often, in milliseconds, instead of after every record
+    #[arg(long)]
+    flush_interval: Option<u64>,
+
+    /// What separates records in the output: newline (NDJSON) or nul
+    #[arg(long, value_enum, default_value_t = RecordDelimiter::Newline)]
+    record_delimiter: RecordDelimiter,
+
+    /// How responses are encoded: json, or length-delimited protobuf frames (gRPC transport
+    /// only; much smaller, but not line-based)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    output_format: OutputFormat,
+
+    /// Write a metadata header record (tool version, chat/video ID, schema version) as the first
+    /// line of a new output
+    #[arg(long)]
+    write_header: bool,
+
+    /// Write a stream info record (chat/video ID, title, channel, viewer count) at the start of
+    /// the run, after the header if any
+    #[arg(long)]
+    emit_stream_info: bool,
+
+    /// Add a `seq` number to each written response, and a `gap` number that increases at every
+    /// reconnect, for detecting lost messages (continued across --resume)
+    #[arg(long)]
+    sequence: bool,
+
+    /// After each response, write a compact record for every new member and membership
+    /// milestone in it (event, channel ID, member level, months, message)
+    #[arg(long)]
+    emit_member_events: bool,
+
+    /// User-Agent header sent with REST API requests
+    #[arg(long, default_value = USER_AGENT)]
+    user_agent: String,
+
+    /// Largest gRPC response accepted, in bytes (busy streams can exceed tonic's 4MB default)
+    #[arg(long, default_value_t = DEFAULT_MAX_MESSAGE_SIZE)]
+    max_message_size: usize,
+
+    /// PEM client certificate presented to gRPC servers that require mutual TLS
+    #[arg(long, requires = "tls_client_key")]
+    tls_client_cert: Option<String>,
+
+    /// PEM private key for --tls-client-cert
+    #[arg(long, requires = "tls_client_cert")]
+    tls_client_key: Option<String>,
+
+    /// Extra header sent with REST API requests, as "Key: Value" (can be repeated)
+    #[arg(long = "header", value_name = "HEADER")]
+    headers: Vec<String>,
+
+    /// Proxy for REST API requests (http://, https:// or socks5:// URL). Overrides the
+    /// HTTPS_PROXY/ALL_PROXY environment variables. Not applied to the gRPC stream.
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Fail any REST request (videos.list, search, REST polling, webhooks) that takes longer
+    /// than this many seconds
+    #[arg(long, default_value_t = DEFAULT_REST_TIMEOUT_SECS)]
+    rest_timeout_secs: u64,
+
+    /// Treat HTTP redirects from REST endpoints as errors instead of following them
+    #[arg(long)]
+    follow_redirects_off: bool,
+
+    /// Try IPv4 addresses before IPv6 ones when connecting (gRPC and REST), for networks
+    /// where IPv6 is advertised but broken
+    #[arg(long)]
+    prefer_ipv4: bool,
+
+    /// Give up on resolving a host name (gRPC and REST) after this many seconds
+    #[arg(long)]
+    dns_timeout_secs: Option<u64>,
+
+    /// Log a warning if no response (including empty ones) arrives for this many seconds
+    #[arg(long)]
+    idle_warn_secs: Option<u64>,
+
+    /// Write a {"event":"heartbeat"} record to the output whenever no chat item has been written
+    /// for this many seconds
+    #[arg(long)]
+    heartbeat_secs: Option<u64>,
+
+    /// Path to a JSON status file (pid, connection state, last message time) updated periodically
+    #[arg(long)]
+    status_file: Option<String>,
+
+    /// Only output messages from authors with these roles, e.g. "moderator,owner"
+    #[arg(long, value_enum, value_delimiter = ',')]
+    only_roles: Vec<Role>,
+
+    /// Only output items matching this expression, e.g.
+    /// 'type == "superChatEvent" && amount_micros > 5000000 || author.is_moderator'
+    #[arg(long, value_parser = expr::Expr::parse)]
+    filter: Option<expr::Expr>,
+
+    /// Only output items whose message contains this word, case-insensitively (can be repeated;
+    /// any match keeps the item)
+    #[arg(long = "match-word", value_name = "WORD")]
+    match_words: Vec<String>,
+
+    /// Only output items whose message matches this regular expression (combined with
+    /// --match-word, matching either keeps the item)
+    #[arg(long, value_parser = regex::Regex::new)]
+    match_regex: Option<regex::Regex>,
+
+    /// Drop items whose message contains this word, case-insensitively (can be repeated)
+    #[arg(long = "exclude-word", value_name = "WORD")]
+    exclude_words: Vec<String>,
+
+    /// Only output items that don't refer to an earlier message (see --threads-only)
+    #[arg(long, conflicts_with = "threads_only")]
+    top_level_only: bool,
+
+    /// Only output items that refer to an earlier message, such as received gift memberships
+    /// (linked to the gifting message) and deletions
+    #[arg(long)]
+    threads_only: bool,
+
+    /// Sort the items of each response by publishedAt before writing (never across responses)
+    #[arg(long)]
+    sort_by_published: bool,
+
+    /// Add `author_details.first_seen` to each item: true for an author's first message in
+    /// this session (or, with --resume, in the archive)
+    #[arg(long)]
+    mark_first_seen: bool,
+
+    /// Only keep messages from authors appearing for the first time (implies --mark-first-seen)
+    #[arg(long)]
+    new_chatters_only: bool,
+
+    /// Replace author channel IDs and names with stable pseudonyms before output (for sharing
+    /// archives)
+    #[arg(long)]
+    anonymize: bool,
+
+    /// Secret salt for --anonymize, so the same author gets the same pseudonym across runs
+    /// (random per run when omitted)
+    #[arg(long, requires = "anonymize")]
+    anonymize_salt: Option<String>,
+
+    /// Replace each item's nested author_details with top-level author_* fields (e.g.
+    /// author_name, author_channel_id, author_is_chat_moderator) and add the message text as
+    /// `message`, for loading items into flat tables
+    #[arg(long)]
+    flatten_author: bool,
+
+    /// POST each chat item as JSON to this URL (delivered in the background)
+    #[arg(long)]
+    post_url: Option<String>,
+
+    /// Extra header sent with webhook POSTs, as "Key: Value" (can be repeated)
+    #[arg(long = "post-header", value_name = "HEADER", requires = "post_url")]
+    post_headers: Vec<String>,
+
+    /// Maximum number of webhook POSTs in flight at once
+    #[arg(long, default_value = "4", requires = "post_url")]
+    post_concurrency: usize,
+
+    /// Times to retry a failed webhook POST, with exponential backoff (default: no retries)
+    #[arg(long, default_value = "0", requires = "post_url")]
+    post_retries: u32,
+
+    /// Only POST paid messages (Super Chats and Super Stickers) worth at least this many
+    /// micros of their currency; the output and other sinks still get everything
+    #[arg(long, requires = "post_url")]
+    min_superchat_micros: Option<u64>,
+
+    /// Start at most this many webhook POSTs per second (fractions allowed), queuing the rest
+    /// and dropping them once the queue is full (default: unlimited)
+    #[arg(long, value_name = "PER_SEC", requires = "post_url")]
+    relay_rate: Option<f64>,
+
+    /// Produce each chat item to Kafka through these bootstrap servers (comma-separated; needs
+    /// the `kafka` feature)
+    #[arg(long, requires = "kafka_topic")]
+    kafka_brokers: Option<String>,
+
+    /// Kafka topic for --kafka-brokers (records are keyed by the author's channel ID)
+    #[arg(long, requires = "kafka_brokers")]
+    kafka_topic: Option<String>,
+
+    /// Also write each chat item to `<dir>/<snippet.type>.ndjson`, one file per message type
+    #[arg(long)]
+    split_by_type_dir: Option<String>,
+
+    /// Connect but discard messages until SIGUSR1 is received (SIGUSR1 toggles pausing)
+    #[arg(long)]
+    start_paused: bool,
+
+    /// On shutdown, print Super Chat totals per currency and the top contributors
+    #[arg(long)]
+    superchat_summary: bool,
+
+    /// Write the details of failed REST/gRPC requests (API key redacted) to timestamped files
+    /// in this directory
+    #[arg(long)]
+    debug_dump_dir: Option<String>,
+
+    /// Print the API calls made and estimated quota units used every this many seconds
+    #[arg(long)]
+    quota_report_secs: Option<u64>,
+
+    /// Write a {"event":"stats"} record with the current viewer and like counts every this many
+    /// seconds (one videos.list call, 1 quota unit, each time)
+    #[arg(long)]
+    sample_stats_secs: Option<u64>,
+
+    /// Print a fatal error as a JSON object with a stable "code" on stderr instead of free text
+    #[arg(long)]
+    json_errors: bool,
+
+    /// Print the effective configuration (addresses, auth, output, reconnect settings) as JSON
+    /// to stderr and exit without connecting. Secrets are redacted.
+    #[arg(long)]
+    print_config: bool,
+
+    /// Exit after writing the first response with messages instead of streaming continuously
+    /// (never reconnects)
+    #[arg(long)]
+    once: bool,
+
+    /// With --once, accept an empty response after waiting this many seconds for messages
+    #[arg(long, default_value = "30", requires = "once")]
+    once_wait_secs: u64,
+
+    /// Stop after running this long (e.g. "2h", "90m"), shutting down as on SIGTERM
+    #[arg(long, value_parser = humantime::parse_duration)]
+    max_duration: Option<tokio::time::Duration>,
+
+    /// Stop (as on SIGTERM, then exit with an error) when a response is malformed, e.g. an
+    /// item without an ID or a snippet without a type, instead of warning and writing it
+    #[arg(long)]
+    strict: bool,
+}
+
+/// Tools that run instead of streaming, mostly working offline on archives
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Convert an NDJSON archive to CSV (one row per chat item)
+    Convert {
+        /// NDJSON archive written by the fetcher
+        #[arg(long)]
+        input: String,
+
+        /// CSV file to write (default: stdout)
+        #[arg(long)]
+        output: Option<String>,
+
+        /// Format the archive was written in (--output-format)
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        input_format: OutputFormat,
+
+        /// What separates records in the archive (--record-delimiter)
+        #[arg(long, value_enum, default_value_t = RecordDelimiter::Newline)]
+        record_delimiter: RecordDelimiter,
+    },
+    /// Replay an NDJSON archive to stdout at its original timing
+    Replay {
+        /// NDJSON archive written by the fetcher
+        #[arg(long)]
+        input: String,
+
+        /// Playback speed multiplier (2 replays twice as fast)
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+
+        /// Format the archive was written in (--output-format)
+        #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+        input_format: OutputFormat,
+
+        /// What separates records in the archive (--record-delimiter); also used for the
+        /// replayed records
+        #[arg(long, value_enum, default_value_t = RecordDelimiter::Newline)]
+        record_delimiter: RecordDelimiter,
+    },
+    /// Follow an archive another instance is writing and copy new lines to stdout (like tail -F)
+    Tail {
+        /// NDJSON archive to follow
+        #[arg(long)]
+        input: String,
+
+        /// Emit the lines already in the file before following (default: only new lines)
+        #[arg(long)]
+        from_start: bool,
+
+        /// What separates records in the archive (--record-delimiter); also used for the
+        /// copied records
+        #[arg(long, value_enum, default_value_t = RecordDelimiter::Newline)]
+        record_delimiter: RecordDelimiter,
+    },
+    /// List a channel's live and upcoming broadcasts
+    ListLive {
+        /// YouTube channel ID (starts with UC)
+        #[arg(long)]
+        channel_id: String,
+
+        /// Path to file containing YouTube API key ("-" reads it from stdin)
+        #[arg(long)]
+        api_key_path: Option<String>,
+
+        /// Path to a file containing an OAuth access token, so private and unlisted
+        /// broadcasts show their start times too
+        #[arg(long)]
+        access_token_path: Option<String>,
+
+        /// Proxy for the requests (http://, https:// or socks5:// URL)
+        #[arg(long)]
+        proxy: Option<String>,
+
+        /// Print one JSON object per broadcast instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check that the binary works end to end against a built-in mock server
+    SelfTest,
 }
 
 /// Macro to attempt reconnection and restart stream
 macro_rules! attempt_reconnect {
-    ($server_url:expr, $api_key:expr, $chat_id:expr, $page_token:expr, $stream:expr, $reconnect_until:expr, $reconnect_secs:expr) => {{
+    ($config:expr, $api_key_path:expr, $video_id:expr, $refresh_chat_id:expr, $chat_id:expr, $page_token:expr, $stream:expr, $reconnect_until:expr, $reconnector:expr, $gave_up:expr, $stats:expr) => {{
+        $stats.reconnects += 1;
+        if let Some(ref mut sequence) = $stats.sequence {
+            sequence.mark_gap();
+        }
+
+        // Re-read the API key file so a rotated key is picked up
+        if let Some(ref path) = $api_key_path {
+            reload_api_key(path, &mut $config.api_key);
+        }
+
+        // Look up the chat ID again when requested (e.g. on SIGHUP)
+        if std::mem::take(&mut $refresh_chat_id) {
+            if let Some(ref video_id) = $video_id {
+                refresh_chat_id(&$config, video_id, &mut $chat_id).await;
+            }
+        }
+
         // Attempt to reconnect and restart stream with pagination token
-        match YouTubeClient::connect($server_url.clone(), $api_key.clone()).await {
-            Ok(mut new_client) => {
-                match new_client
-                    .stream_comments(Some($chat_id.clone()), $page_token.clone())
-                    .await
-                {
-                    Ok(new_stream) => {
-                        $stream = new_stream;
-                        eprintln!("Reconnected successfully");
+        match stream::open_stream(&$config, &$chat_id, $page_token.clone()).await {
+            Ok(new_stream) => {
+                $stream = new_stream;
+                log!("Reconnected successfully");
+            }
+            Err(e) => {
+                log!("Failed to reconnect: {}", e);
+
+                // The video may have a new chat now (e.g. a premiere that became a live stream)
+                if $video_id.is_some() && stream::is_chat_not_found(e.as_ref()) {
+                    log!(
+                        "Chat ID {} was not found; looking it up again before retrying",
+                        $chat_id
+                    );
+                    $refresh_chat_id = true;
+                }
+
+                // Schedule another reconnection attempt, unless the policy gives up
+                match $reconnector.next_delay(e.as_ref()) {
+                    Some(delay) => {
+                        let delay = reconnect_delay(delay, $stats.polling_interval_millis);
+                        log!("Retrying in {} seconds...", delay.as_secs());
+                        $reconnect_until = Some(tokio::time::Instant::now() + delay);
                     }
-                    Err(e) => {
-                        eprintln!("Failed to restart stream after reconnection: {}", e);
-                        // Schedule another reconnection attempt
-                        $reconnect_until = Some(
-                            tokio::time::Instant::now()
-                                + tokio::time::Duration::from_secs($reconnect_secs),
-                        );
+                    None => {
+                        $gave_up = Some(e.to_string());
+                        break;
                     }
                 }
             }
-            Err(e) => {
-                eprintln!("Failed to reconnect: {}", e);
-                // Schedule another reconnection attempt
-                $reconnect_until = Some(
-                    tokio::time::Instant::now() + tokio::time::Duration::from_secs($reconnect_secs),
-                );
-            }
         }
     }};
 }
 
+/// Macro to schedule a reconnect after the stream is lost, as the reconnect policy decides.
+/// A zero delay reconnects on the next loop iteration without arming the timer (and without
+/// honoring the polling interval). Breaks out of the loop if the policy gives up.
+macro_rules! schedule_reconnect {
+    ($error:expr, $reconnect_until:ident, $reconnect_now:ident, $reconnector:expr, $gave_up:expr, $stats:expr) => {
+        match $reconnector.next_delay($error) {
+            Some(delay) if delay.is_zero() => {
+                log!("Reconnecting immediately...");
+                $reconnect_now = true;
+            }
+            Some(delay) => {
+                let delay = reconnect_delay(delay, $stats.polling_interval_millis);
+                log!("Waiting {} seconds before reconnecting...", delay.as_secs());
+                $reconnect_until = Some(tokio::time::Instant::now() + delay);
+            }
+            None => {
+                $gave_up = Some($error.to_string());
+                break;
+            }
+        }
+    };
+}
+
 /// Macro to handle stream messages (avoids code duplication)
 macro_rules! handle_stream_message {
-    ($stream_result:expr, $next_page_token:ident, $reconnect_until:ident, $reconnect_wait_secs:expr, $output_file:expr) => {
+    ($stream_result:expr, $next_page_token:ident, $reconnect_until:ident, $reconnect_now:ident, $reconnector:expr, $gave_up:expr, $stream_ends:expr, $output:expr, $stats:expr, $filter:expr, $sinks:expr, $paused:expr, $resume_fallback:expr) => {
         match $stream_result {
             Some(Ok(message)) => {
-                // Update the page token for potential reconnection
-                $next_page_token = message.next_page_token.clone();
-
-                // Check if the response contains any items
-                if message.items.is_empty() {
-                    // Log empty response to stderr instead of stdout
-                    eprintln!("Received empty response (no items)");
-                } else {
-                    // Print message as JSON (non-delimited)
-                    let json = serde_json::to_string(&message)?;
-
-                    // Write to file or stdout
-                    if let Some(ref mut file) = $output_file {
-                        writeln!(file, "{}", json)?;
-                        file.flush()?;
-                    } else {
-                        println!("{}", json);
-                    }
-                }
+                $reconnector.reset();
+                // The resumed page token was accepted
+                $resume_fallback = None;
+                process_response(
+                    message,
+                    &mut $next_page_token,
+                    &mut $output,
+                    &mut $stats,
+                    &mut $filter,
+                    &mut $sinks,
+                    $paused,
+                )
+                .await?;
             }
             Some(Err(e)) => {
                 // Stream error (timeout or connection issue during streaming)
-                eprintln!(
-                    "Error receiving message: {}\nConnection lost. Waiting {} seconds before reconnecting...",
-                    e, $reconnect_wait_secs
-                );
+                log!("Error receiving message: {}\nConnection lost.", e);
+
+                if stream::is_page_token_invalid(e.as_ref())
+                    && let Some(since) = $resume_fallback.take()
+                {
+                    fall_back_to_timestamp(since, &mut $next_page_token);
+                    $filter.since = Some(since);
+                }
 
                 // Log pagination status
                 if let Some(ref token) = $next_page_token {
-                    eprintln!("Will resume from page token: {}", token);
+                    log!("Will resume from page token: {}", token);
                 }
 
-                // Schedule reconnection
-                $reconnect_until = Some(
-                    tokio::time::Instant::now()
-                        + tokio::time::Duration::from_secs($reconnect_wait_secs),
-                );
-            }
-            None => {
-                // Stream ended (timeout or connection closed)
-                eprintln!(
-                    "Stream ended. Waiting {} seconds before reconnecting...",
-                    $reconnect_wait_secs
-                );
+                // Mark the gap in-band for downstream consumers
+                $output.write_control_event(serde_json::json!({
+                    "event": "reconnect",
+                    "reason": e.to_string(),
+                    "last_page_token": $next_page_token,
+                }))
+                .await?;
+
+                // Schedule reconnection
+                schedule_reconnect!(e.as_ref(), $reconnect_until, $reconnect_now, $reconnector, $gave_up, $stats);
+            }
+            None => {
+                // Stream ended (timeout or connection closed)
+                log!("Stream ended.");
+
+                // Log pagination status
+                if let Some(ref token) = $next_page_token {
+                    log!("Will resume from page token: {}", token);
+                }
+
+                // Mark the gap in-band for downstream consumers
+                $output.write_control_event(serde_json::json!({
+                    "event": "reconnect",
+                    "reason": "stream ended",
+                    "last_page_token": $next_page_token,
+                }))
+                .await?;
+
+                // Back off for longer if the stream keeps ending straight away (e.g. a dead chat)
+                if $stream_ends.record(tokio::time::Instant::now()) {
+                    log!(
+                        "Stream ended {} times within {} seconds; cooling down for {} seconds before reconnecting",
+                        stream::STREAM_END_LIMIT,
+                        stream::STREAM_END_WINDOW.as_secs(),
+                        $stream_ends.cooldown.as_secs()
+                    );
+                    $reconnect_until = Some(tokio::time::Instant::now() + $stream_ends.cooldown);
+                } else {
+                    // Schedule reconnection
+                    schedule_reconnect!(&reconnect::StreamEnded, $reconnect_until, $reconnect_now, $reconnector, $gave_up, $stats);
+                }
+            }
+        }
+    };
+}
+
+/// Read the API key from a file, or from stdin when the path is "-"
+fn read_api_key(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    use std::io::{IsTerminal, Read};
+
+    if path == "-" {
+        let mut stdin = std::io::stdin();
+        if stdin.is_terminal() {
+            return Err("--api-key-path - expects the API key to be piped via stdin, but stdin is a terminal".into());
+        }
+
+        log!("Reading API key from stdin");
+        let mut key = String::new();
+        stdin
+            .read_to_string(&mut key)
+            .map_err(|e| format!("Failed to read API key from stdin: {}", e))?;
+        redact::add_secret(&key);
+        return Ok(key.trim().to_string());
+    }
+
+    log!("Reading API key from: {}", path);
+    let key = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read API key file '{}': {}", path, e))?
+        .trim()
+        .to_string();
+    redact::add_secret(&key);
+    Ok(key)
+}
+
+/// Re-read the API key file, keeping the current key if it can't be read.
+/// A key read from stdin can't be reloaded.
+fn reload_api_key(path: &str, api_key: &mut Option<String>) {
+    if path == "-" {
+        return;
+    }
+
+    match read_api_key(path) {
+        Ok(key) => *api_key = Some(key),
+        Err(e) => log!("Warning: {}; keeping the previously loaded API key", e),
+    }
+}
+
+/// Look up the chat ID for the video again, keeping the current one if the lookup fails
+async fn refresh_chat_id(config: &StreamConfig, video_id: &str, chat_id: &mut String) {
+    match fetch_stream_info(config, video_id).await {
+        Ok(rest::StreamInfo {
+            chat_id: new_chat_id,
+            ..
+        }) => {
+            if new_chat_id != *chat_id {
+                log!("Chat ID changed: {} -> {}", chat_id, new_chat_id);
+                *chat_id = new_chat_id;
+            }
+        }
+        Err(e) => log!("Failed to refresh chat ID, keeping {}: {}", chat_id, e),
+    }
+}
+
+/// Look up the video with the stream's credentials, reading the access token file if there is one
+async fn fetch_stream_info(
+    config: &StreamConfig,
+    video_id: &str,
+) -> Result<rest::StreamInfo, Box<dyn std::error::Error>> {
+    let access_token = config
+        .access_token_path
+        .as_deref()
+        .map(read_access_token)
+        .transpose()?;
+    rest::fetch_stream_info(
+        &config.http_client,
+        &config.rest_api_address,
+        video_id,
+        config.api_key.as_deref(),
+        access_token.as_deref(),
+        config.debug_dump.as_ref(),
+        &config.api_calls,
+    )
+    .await
+}
+
+/// Read an OAuth access token from a file
+fn read_access_token(path: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let token = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read access token file '{}': {}", path, e))?;
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(errors::CodedError::new(
+            "unauthorized",
+            format!("Access token file '{}' is empty", path),
+        )
+        .into());
+    }
+    redact::add_secret(token);
+    Ok(token.to_string())
+}
+
+/// Handle one response: track pagination and polling interval, then write it out if non-empty
+async fn process_response(
+    mut message: serde_json::Value,
+    next_page_token: &mut Option<String>,
+    output: &mut Output,
+    stats: &mut Stats,
+    filter: &mut ItemFilter,
+    sinks: &mut [Box<dyn MessageSink>],
+    paused: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Update the page token for potential reconnection
+    *next_page_token = message
+        .get("next_page_token")
+        .and_then(|token| token.as_str())
+        .map(|s| s.to_string());
+    stats.responses += 1;
+    stats.last_response_at = Some(std::time::SystemTime::now());
+
+    // Track the server's requested polling interval so reconnects don't outpace it
+    let polling_interval = message
+        .get("polling_interval_millis")
+        .and_then(|ms| ms.as_u64());
+    if stats.record_polling_interval(polling_interval) {
+        log!(
+            "Server polling interval: {}ms",
+            polling_interval.unwrap_or_default()
+        );
+    }
+
+    // While paused the stream keeps its position, but nothing is written
+    if paused {
+        return Ok(());
+    }
+
+    filter.apply(&mut message);
+
+    // Check if the response contains any items
+    let item_count = message
+        .get("items")
+        .and_then(|items| items.as_array())
+        .map_or(0, |items| items.len());
+    if item_count == 0 {
+        // Log empty response to stderr instead of stdout
+        log!("Received empty response (no items)");
+    } else {
+        stats.items += item_count as u64;
+        stats.last_message_at = stats.last_response_at;
+
+        if let Some(ref mut super_chats) = stats.super_chats {
+            for item in message::items(&message) {
+                super_chats.record(message::Item(item));
+            }
+        }
+
+        for sink in sinks.iter_mut() {
+            sink.offer(&message);
+        }
+
+        if let Some(ref mut sequence) = stats.sequence {
+            sequence.stamp(&mut message);
+        }
+
+        // Write to file or stdout
+        output.write_response(&message).await?;
+        output.write_member_events(&message).await?;
+        stats.dropped = output.dropped();
+    }
+
+    Ok(())
+}
+
+/// Compute the wait before reconnecting, never shorter than the server's polling interval
+fn reconnect_delay(
+    configured: tokio::time::Duration,
+    polling_interval_millis: Option<u64>,
+) -> tokio::time::Duration {
+    let requested = tokio::time::Duration::from_millis(polling_interval_millis.unwrap_or(0));
+    configured.max(requested)
+}
+
+/// Check whether a line is a control record written by --emit-control-events
+fn is_control_event(line: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(line).is_ok_and(|value| value.get("event").is_some())
+}
+
+/// Read the last chat response record from a file, skipping control records
+fn read_last_line(
+    path: &str,
+    delimiter: RecordDelimiter,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(records) = records_from_end(path, delimiter)? else {
+        return Ok(None);
+    };
+
+    // Get the first non-empty line from the end
+    for line in records {
+        let line = line?;
+        if !line.trim().is_empty() && !is_control_event(&line) {
+            return Ok(Some(line));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Find the most recent chat ID in a file, scanning back past responses that don't carry one
+/// (e.g. empty responses). Header and stream info records are used as a last resort.
+fn find_last_chat_id(
+    path: &str,
+    delimiter: RecordDelimiter,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(records) = records_from_end(path, delimiter)? else {
+        return Ok(None);
+    };
+
+    for line in records {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let chat_id = if is_control_event(&line) {
+            serde_json::from_str::<serde_json::Value>(&line)?
+                .get("chat_id")
+                .and_then(|id| id.as_str())
+                .map(|s| s.to_string())
+        } else {
+            // Skip lines that aren't valid JSON (e.g. a truncated final write)
+            parse_resume_info(&line)
+                .ok()
+                .and_then(|(chat_id, _)| chat_id)
+        };
+        if chat_id.is_some() {
+            return Ok(chat_id);
+        }
+    }
+
+    Ok(None)
+}
+
+/// Records of an archive, read from the end
+type RecordsFromEnd = rev_lines::RevLines<std::io::BufReader<DelimiterAsNewline<std::fs::File>>>;
+
+/// Iterate over the records of a file from the end. Returns None if the file doesn't exist.
+fn records_from_end(
+    path: &str,
+    delimiter: RecordDelimiter,
+) -> Result<Option<RecordsFromEnd>, Box<dyn std::error::Error>> {
+    let file = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    // RevLines only splits on newlines; serialized JSON never contains a raw newline, so
+    // other delimiters can be mapped onto it
+    let reader = std::io::BufReader::new(DelimiterAsNewline::new(file, delimiter));
+    Ok(Some(rev_lines::RevLines::new(reader)))
+}
+
+/// Resume from a protobuf archive: the page token of the last frame, and the chat ID of the
+/// last frame that carries one. Frames can only be read forwards, so this reads the whole file.
+fn resume_from_frames(
+    path: &str,
+) -> Result<(Option<String>, Option<String>), Box<dyn std::error::Error>> {
+    let Some(frames) = protobuf::open(path)? else {
+        log!("Output file is empty or does not exist yet");
+        return Ok((None, None));
+    };
+
+    let mut chat_id = None;
+    let mut page_token = None;
+    let mut count = 0u64;
+    for frame in frames {
+        let (frame_chat_id, frame_token) = resume_info_from_value(&frame);
+        chat_id = frame_chat_id.or(chat_id);
+        page_token = frame_token;
+        count += 1;
+    }
+
+    if count == 0 {
+        log!("Output file is empty or does not exist yet");
+        return Ok((None, None));
+    }
+    match chat_id {
+        Some(ref cid) => {
+            log!("Resuming with chat ID: {}", cid);
+            if let Some(ref t) = page_token {
+                log!("Resuming from page token: {}", t);
+            }
+            Ok((chat_id, page_token))
+        }
+        None => {
+            log!("Could not find a chat ID in the file");
+            Ok((None, None))
+        }
+    }
+}
+
+/// The latest `published_at` among a response's items
+fn last_published_at(value: &serde_json::Value) -> Option<std::time::SystemTime> {
+    value
+        .get("items")?
+        .as_array()?
+        .iter()
+        .filter_map(|item| message::Item(item).published_at())
+        .filter_map(|at| humantime::parse_rfc3339_weak(at).ok())
+        .max()
+}
+
+/// The publish time of the last message written to an archive, scanning back past empty
+/// responses and control events
+fn find_last_published_at(
+    path: &str,
+    format: OutputFormat,
+    delimiter: RecordDelimiter,
+) -> Result<Option<std::time::SystemTime>, Box<dyn std::error::Error>> {
+    if format == OutputFormat::Protobuf {
+        let Some(frames) = protobuf::open(path)? else {
+            return Ok(None);
+        };
+        return Ok(frames.filter_map(|frame| last_published_at(&frame)).last());
+    }
+
+    let Some(records) = records_from_end(path, delimiter)? else {
+        return Ok(None);
+    };
+    for line in records {
+        let line = line?;
+        if line.trim().is_empty() || is_control_event(&line) {
+            continue;
+        }
+        // Skip lines that aren't valid JSON (e.g. a truncated final write)
+        let published_at = serde_json::from_str(&line)
+            .ok()
+            .and_then(|value| last_published_at(&value));
+        if published_at.is_some() {
+            return Ok(published_at);
+        }
+    }
+    Ok(None)
+}
+
+/// Author tracking for --mark-first-seen, seeded with the authors already in the archive when
+/// resuming so a restart doesn't mark everyone as new
+fn first_seen_from_archive(args: &Args) -> Result<filter::FirstSeen, Box<dyn std::error::Error>> {
+    let mut first_seen = filter::FirstSeen::new(args.new_chatters_only);
+    let path = match args.output_file {
+        Some(ref path) if args.resume => path,
+        _ => return Ok(first_seen),
+    };
+
+    if args.output_format == OutputFormat::Protobuf {
+        if let Some(frames) = protobuf::open(path)? {
+            frames.for_each(|frame| first_seen.seed(&frame));
+        }
+    } else if let Some(records) = records_from_end(path, args.record_delimiter)? {
+        for line in records {
+            let line = line?;
+            if line.trim().is_empty() || is_control_event(&line) {
+                continue;
+            }
+            // Skip lines that aren't valid JSON (e.g. a truncated final write)
+            if let Ok(response) = serde_json::from_str(&line) {
+                first_seen.seed(&response);
+            }
+        }
+    }
+    log!("Seeded {} known authors from '{}'", first_seen.len(), path);
+    Ok(first_seen)
+}
+
+/// Give up on a rejected resume page token: stream from the live edge instead, dropping
+/// messages published before `since`
+fn fall_back_to_timestamp(since: std::time::SystemTime, page_token: &mut Option<String>) {
+    log!(
+        "The saved page token was rejected; streaming from now and skipping messages published before {}",
+        humantime::format_rfc3339(since)
+    );
+    *page_token = None;
+}
+
+/// Parse resume information from the last JSON line
+fn parse_resume_info(
+    json_line: &str,
+) -> Result<(Option<String>, Option<String>), Box<dyn std::error::Error>> {
+    let value: serde_json::Value = serde_json::from_str(json_line)?;
+    Ok(resume_info_from_value(&value))
+}
+
+/// The chat ID and next page token of a response
+fn resume_info_from_value(value: &serde_json::Value) -> (Option<String>, Option<String>) {
+    // Extract live_chat_id from items[0].snippet.live_chat_id
+    // Try both snake_case (live_chat_id) and camelCase (liveChatId) for compatibility
+    let chat_id = value
+        .get("items")
+        .and_then(|items| items.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|item| item.get("snippet"))
+        .and_then(|snippet| {
+            snippet
+                .get("live_chat_id")
+                .or_else(|| snippet.get("liveChatId"))
+        })
+        .and_then(|id| id.as_str())
+        .map(|s| s.to_string());
+
+    // Extract next_page_token (try both snake_case and camelCase)
+    let next_page_token = value
+        .get("nextPageToken")
+        .or_else(|| value.get("next_page_token"))
+        .and_then(|token| token.as_str())
+        .map(|s| s.to_string());
+
+    (chat_id, next_page_token)
+}
+
+/// The reconnect policy the run uses (--once never reconnects)
+fn reconnect_policy(args: &Args) -> reconnect::PolicyKind {
+    if args.once {
+        reconnect::PolicyKind::None
+    } else {
+        args.reconnect_policy
+    }
+}
+
+/// Get REST API address from environment variable or use default
+fn rest_api_address() -> String {
+    std::env::var("REST_API_ADDRESS").unwrap_or_else(|_| "https://www.googleapis.com".to_string())
+}
+
+/// Get gRPC server address from environment variable or use default
+fn server_url() -> String {
+    // Note: For TLS-enabled gRPC connections, tonic requires https:// prefix
+    let server_address = std::env::var("SERVER_ADDRESS")
+        .unwrap_or_else(|_| "https://youtube.googleapis.com".to_string());
+    if server_address.starts_with("http://")
+        || server_address.starts_with("https://")
+        || server_address.starts_with("unix://")
+    {
+        server_address
+    } else {
+        // Default to https:// for secure connections
+        format!("https://{}", server_address)
+    }
+}
+
+/// Choose one of the channel's live videos according to `pick`
+fn choose_live_video(
+    channel_id: &str,
+    videos: &[rest::LiveVideo],
+    pick: rest::Pick,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match (videos, pick) {
+        ([], _) => Err(format!("Channel {} is not live right now", channel_id).into()),
+        ([video], _) | ([video, ..], rest::Pick::First) => {
+            log!(
+                "Found live video: {} ({})",
+                video.video_id,
+                video.title.as_deref().unwrap_or("untitled")
+            );
+            Ok(video.video_id.clone())
+        }
+        (videos, rest::Pick::Only) => {
+            log!("Channel {} has {} live videos:", channel_id, videos.len());
+            for video in videos {
+                log!(
+                    "  {}  {}",
+                    video.video_id,
+                    video.title.as_deref().unwrap_or("untitled")
+                );
+            }
+            Err("Pass one of them with --video-id, or use --pick first".into())
+        }
+    }
+}
+
+/// Reject impossible or meaningless flag combinations before any file or network work
+fn validate_args(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    if !args.resume && args.video_id.is_none() && args.channel_id.is_none() {
+        return Err("Either --video-id, --channel-id or --resume must be specified".into());
+    }
+
+    // Catch pasted URLs and stray characters before they reach videos.list
+    if let Some(ref video_id) = args.video_id
+        && (video_id.is_empty()
+            || !video_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+    {
+        return Err(format!(
+            "Invalid --video-id '{}': video IDs contain only letters, digits, '-' and '_'",
+            video_id
+        )
+        .into());
+    }
+
+    if args.resume && args.output_file.is_none() {
+        return Err("--output-file must be specified when using --resume".into());
+    }
+
+    if let Some(path) = args.output_file.as_deref()
+        && args.resume
+        && args.video_id.is_none()
+        && args.channel_id.is_none()
+        && !std::path::Path::new(path).is_file()
+    {
+        return Err(format!(
+            "--resume without --video-id needs an existing output file, but '{}' does not exist",
+            path
+        )
+        .into());
+    }
+
+    if args.flush_every == Some(0) || args.flush_interval == Some(0) {
+        return Err("--flush-every and --flush-interval must be greater than zero".into());
+    }
+
+    if args.idle_warn_secs == Some(0) {
+        return Err("--idle-warn-secs must be greater than zero".into());
+    }
+
+    if args.output_format == OutputFormat::Protobuf {
+        if args.transport != Transport::Grpc {
+            return Err("--output-format protobuf needs --transport grpc".into());
+        }
+        // These write JSON records alongside the responses, or reshape items into fields the
+        // proto doesn't have (which encoding would silently drop)
+        let json_records = [
+            (args.flatten_author, "--flatten-author"),
+            (args.write_header, "--write-header"),
+            (args.emit_stream_info, "--emit-stream-info"),
+            (args.emit_control_events, "--emit-control-events"),
+            (args.emit_member_events, "--emit-member-events"),
+            (args.heartbeat_secs.is_some(), "--heartbeat-secs"),
+            (args.sample_stats_secs.is_some(), "--sample-stats-secs"),
+            (args.sequence, "--sequence"),
+        ];
+        if let Some((_, flag)) = json_records.iter().find(|(set, _)| *set) {
+            return Err(format!("{} can't be used with --output-format protobuf", flag).into());
+        }
+    }
+
+    if args.rest_timeout_secs == 0 {
+        return Err("--rest-timeout-secs must be greater than zero".into());
+    }
+
+    if args.max_output_bytes == Some(0) {
+        return Err("--max-output-bytes must be greater than zero".into());
+    }
+
+    if args.dns_timeout_secs == Some(0) {
+        return Err("--dns-timeout-secs must be greater than zero".into());
+    }
+
+    if args.heartbeat_secs == Some(0) {
+        return Err("--heartbeat-secs must be greater than zero".into());
+    }
 
-                // Log pagination status
-                if let Some(ref token) = $next_page_token {
-                    eprintln!("Will resume from page token: {}", token);
-                }
+    if args.quota_report_secs == Some(0) {
+        return Err("--quota-report-secs must be greater than zero".into());
+    }
 
-                // Schedule reconnection
-                $reconnect_until = Some(
-                    tokio::time::Instant::now()
-                        + tokio::time::Duration::from_secs($reconnect_wait_secs),
-                );
-            }
-        }
-    };
-}
+    if args.sample_stats_secs == Some(0) {
+        return Err("--sample-stats-secs must be greater than zero".into());
+    }
 
-/// Read the last line from a file
-fn read_last_line(path: &str) -> Result<Option<String>, Box<dyn std::error::Error>> {
-    use rev_lines::RevLines;
-    use std::io::BufReader;
+    if args.sample_stats_secs.is_some() && args.video_id.is_none() && args.channel_id.is_none() {
+        return Err(
+            "--sample-stats-secs needs --video-id or --channel-id to look the video up".into(),
+        );
+    }
 
-    let file = match std::fs::File::open(path) {
-        Ok(f) => f,
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
-        Err(e) => return Err(e.into()),
-    };
+    if args.start_paused && cfg!(not(unix)) {
+        return Err("--start-paused needs SIGUSR1, which is only available on Unix".into());
+    }
 
-    let reader = BufReader::new(file);
-    let rev_lines = RevLines::new(reader);
+    if args.connect_timeout_secs == 0 {
+        return Err("--connect-timeout-secs must be greater than zero".into());
+    }
 
-    // Get the first non-empty line from the end
-    for line in rev_lines {
-        let line = line?;
-        if !line.trim().is_empty() {
-            return Ok(Some(line));
-        }
+    if args.kafka_brokers.is_some() && cfg!(not(feature = "kafka")) {
+        return Err(
+            "--kafka-brokers needs Kafka support; rebuild with `cargo build --features kafka`"
+                .into(),
+        );
     }
 
-    Ok(None)
-}
+    if args.max_message_size == 0 {
+        return Err("--max-message-size must be greater than zero".into());
+    }
 
-/// Parse resume information from the last JSON line
-fn parse_resume_info(
-    json_line: &str,
-) -> Result<(Option<String>, Option<String>), Box<dyn std::error::Error>> {
-    let value: serde_json::Value = serde_json::from_str(json_line)?;
+    if args.post_concurrency == 0 {
+        return Err("--post-concurrency must be greater than zero".into());
+    }
 
-    // Extract live_chat_id from items[0].snippet.live_chat_id
-    // Try both snake_case (live_chat_id) and camelCase (liveChatId) for compatibility
-    let chat_id = value
-        .get("items")
-        .and_then(|items| items.as_array())
-        .and_then(|arr| arr.first())
-        .and_then(|item| item.get("snippet"))
-        .and_then(|snippet| {
-            snippet
-                .get("live_chat_id")
-                .or_else(|| snippet.get("liveChatId"))
-        })
-        .and_then(|id| id.as_str())
-        .map(|s| s.to_string());
+    if args
+        .relay_rate
+        .is_some_and(|rate| !rate.is_finite() || rate <= 0.0)
+    {
+        return Err("--relay-rate must be a number greater than zero".into());
+    }
 
-    // Extract next_page_token (try both snake_case and camelCase)
-    let next_page_token = value
-        .get("nextPageToken")
-        .or_else(|| value.get("next_page_token"))
-        .and_then(|token| token.as_str())
-        .map(|s| s.to_string());
+    if args.max_memory_messages == Some(0) {
+        return Err("--max-memory-messages must be greater than zero".into());
+    }
+
+    if args.output_buffer_size == 0 {
+        return Err("--output-buffer-size must be greater than zero".into());
+    }
 
-    Ok((chat_id, next_page_token))
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn main() {
     let args = Args::parse();
+    let json_errors = args.json_errors;
+    let grace = tokio::time::Duration::from_secs(args.shutdown_grace_secs);
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to start the async runtime");
 
-    // Validate arguments
-    if !args.resume && args.video_id.is_none() {
-        return Err("Either --video-id or --resume must be specified".into());
+    let result = runtime.block_on(run(args));
+    // Printed here rather than returned, so the error goes through redaction too
+    if let Err(ref e) = result {
+        if json_errors {
+            log!("{}", errors::to_json(e.as_ref()));
+        } else {
+            log!("Error: {:?}", e);
+        }
     }
+    // Dropping the runtime would wait for writer threads indefinitely, and one blocked on a
+    // hung destination (full pipe, stalled network filesystem) would keep the process alive
+    runtime.shutdown_timeout(grace);
+    if result.is_err() {
+        std::process::exit(1);
+    }
+}
 
-    if args.resume && args.output_file.is_none() {
-        return Err("--output-file must be specified when using --resume".into());
+async fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(command) = args.command {
+        return match command {
+            Command::Convert {
+                input,
+                output,
+                input_format,
+                record_delimiter,
+            } => convert::convert_to_csv(&input, output.as_deref(), input_format, record_delimiter),
+            Command::Replay {
+                input,
+                speed,
+                input_format,
+                record_delimiter,
+            } => replay::replay(&input, speed, input_format, record_delimiter).await,
+            Command::Tail {
+                input,
+                from_start,
+                record_delimiter,
+            } => tail::follow(&input, from_start, record_delimiter).await,
+            Command::ListLive {
+                channel_id,
+                api_key_path,
+                access_token_path,
+                proxy,
+                json,
+            } => {
+                let api_key = api_key_path.as_deref().map(read_api_key).transpose()?;
+                let access_token = access_token_path
+                    .as_deref()
+                    .map(read_access_token)
+                    .transpose()?;
+                let client = rest::build_client(
+                    USER_AGENT,
+                    &[],
+                    proxy.as_deref(),
+                    tokio::time::Duration::from_secs(DEFAULT_REST_TIMEOUT_SECS),
+                    true,
+                    yt_grpc_client::NetworkOptions::default(),
+                )?;
+                list_live::list(
+                    &client,
+                    list_live::ListLiveConfig {
+                        rest_api_address: &rest_api_address(),
+                        channel_id: &channel_id,
+                        api_key: api_key.as_deref(),
+                        access_token: access_token.as_deref(),
+                        json,
+                    },
+                )
+                .await
+            }
+            Command::SelfTest => selftest::run().await,
+        };
     }
 
-    // Read API key from file if provided (needed for both REST and gRPC)
-    let api_key = if let Some(api_key_path) = &args.api_key_path {
-        eprintln!("Reading API key from: {}", api_key_path);
-        let key = std::fs::read_to_string(api_key_path)
-            .map_err(|e| format!("Failed to read API key file '{}': {}", api_key_path, e))?
-            .trim()
-            .to_string();
-        Some(key)
-    } else {
-        None
-    };
+    validate_args(&args).map_err(|e| errors::CodedError::new("invalid_arguments", e))?;
 
-    // Open output file if specified
-    let mut output_file = if let Some(ref path) = args.output_file {
-        eprintln!("Output file: {}", path);
-        Some(
-            OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(path)
-                .map_err(|e| format!("Failed to open output file '{}': {}", path, e))?,
-        )
-    } else {
-        None
-    };
+    // Wall-clock limit for the whole run, counted from startup
+    let stop_at = args
+        .max_duration
+        .map(|limit| tokio::time::Instant::now() + limit);
 
     // Try to resume from file if requested
     let (mut chat_id, initial_page_token) = if args.resume {
@@ -230,83 +1363,429 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .output_file
             .as_ref()
             .expect("output_file is guaranteed to be Some when resume is true");
-        eprintln!("Attempting to resume from: {}", output_path);
-
-        match read_last_line(output_path)? {
-            Some(last_line) => {
-                eprintln!("Found last line, parsing resume info...");
-                match parse_resume_info(&last_line) {
-                    Ok((Some(cid), token)) => {
-                        eprintln!("Resuming with chat ID: {}", cid);
-                        if let Some(ref t) = token {
-                            eprintln!("Resuming from page token: {}", t);
+        log!("Attempting to resume from: {}", output_path);
+
+        if args.output_format == OutputFormat::Protobuf {
+            resume_from_frames(output_path)?
+        } else {
+            match read_last_line(output_path, args.record_delimiter)? {
+                Some(last_line) => {
+                    log!("Found last line, parsing resume info...");
+                    match parse_resume_info(&last_line) {
+                        Ok((Some(cid), token)) => {
+                            log!("Resuming with chat ID: {}", cid);
+                            if let Some(ref t) = token {
+                                log!("Resuming from page token: {}", t);
+                            }
+                            (Some(cid), token)
+                        }
+                        Ok((None, token)) => {
+                            // The last response was empty; the chat ID is in an earlier one
+                            log!("No chat ID in last line, searching earlier lines...");
+                            match find_last_chat_id(output_path, args.record_delimiter)? {
+                                Some(cid) => {
+                                    log!("Resuming with chat ID: {}", cid);
+                                    if let Some(ref t) = token {
+                                        log!("Resuming from page token: {}", t);
+                                    }
+                                    (Some(cid), token)
+                                }
+                                None => {
+                                    log!("Could not find a chat ID in the file");
+                                    (None, None)
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            log!("Failed to parse last line: {}", e);
+                            (None, None)
                         }
-                        (Some(cid), token)
-                    }
-                    Ok((None, _)) => {
-                        eprintln!("Could not extract chat ID from last line");
-                        (None, None)
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to parse last line: {}", e);
-                        (None, None)
                     }
                 }
-            }
-            None => {
-                eprintln!("Output file is empty or does not exist yet");
-                (None, None)
+                None => {
+                    log!("Output file is empty or does not exist yet");
+                    (None, None)
+                }
             }
         }
     } else {
         (None, None)
     };
 
+    if args.print_config {
+        print_config::print(&args, chat_id.as_deref(), initial_page_token.as_deref());
+        return Ok(());
+    }
+
+    // Messages published before this are dropped. With --resume, the time is only used once
+    // the saved page token is rejected, and the last saved message's time is preferred.
+    let mut since = args.resume_from;
+    let mut resume_fallback = None;
+    if since.is_some()
+        && initial_page_token.is_some()
+        && let Some(ref output_path) = args.output_file
+    {
+        resume_fallback =
+            find_last_published_at(output_path, args.output_format, args.record_delimiter)?
+                .or(since.take());
+    }
+    if let Some(since) = since {
+        log!(
+            "Skipping messages published before {}",
+            humantime::format_rfc3339(since)
+        );
+    }
+
+    // Without a video ID there is nothing to fall back on, so fail before any network work
+    if chat_id.is_none() && args.video_id.is_none() && args.channel_id.is_none() {
+        return Err(format!(
+            "Could not find a chat ID to resume from in '{}'; pass --video-id to start from a video instead",
+            args.output_file.as_deref().unwrap_or_default()
+        )
+        .into());
+    }
+
+    // Read API key from file if provided (needed for both REST and gRPC)
+    let api_key = if let Some(api_key_path) = &args.api_key_path {
+        Some(read_api_key(api_key_path)?)
+    } else {
+        None
+    };
+
+    // Check the access token up front so a missing or empty file fails before connecting
+    // (it is read again before each use, to pick up refreshes)
+    if let Some(ref path) = args.access_token_path {
+        read_access_token(path)?;
+        log!("Access token file: {}", path);
+    }
+
+    // Load the mTLS client certificate up front so a bad path fails before connecting
+    let client_identity = match (&args.tls_client_cert, &args.tls_client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            let read = |path: &String| {
+                std::fs::read(path)
+                    .map_err(|e| format!("Failed to read TLS client file '{}': {}", path, e))
+            };
+            Some(tonic::transport::Identity::from_pem(
+                read(cert_path)?,
+                read(key_path)?,
+            ))
+        }
+        _ => None,
+    };
+
+    let debug_dump = args
+        .debug_dump_dir
+        .as_deref()
+        .map(debug_dump::DebugDump::new)
+        .transpose()?;
+
+    // One HTTP client, shared by every REST call
+    let rest_timeout = tokio::time::Duration::from_secs(args.rest_timeout_secs);
+    let network = yt_grpc_client::NetworkOptions {
+        prefer_ipv4: args.prefer_ipv4,
+        dns_timeout: args.dns_timeout_secs.map(tokio::time::Duration::from_secs),
+        connect_timeout: Some(tokio::time::Duration::from_secs(args.connect_timeout_secs)),
+    };
+    let http_client = rest::build_client(
+        &args.user_agent,
+        &args.headers,
+        args.proxy.as_deref(),
+        rest_timeout,
+        !args.follow_redirects_off,
+        network,
+    )?;
+
+    // Share of --max-memory-messages for each queue: the output buffer plus one per sink
+    let queues = 1
+        + usize::from(args.post_url.is_some())
+        + usize::from(args.kafka_brokers.is_some())
+        + usize::from(args.split_by_type_dir.is_some());
+    let queue_share = args
+        .max_memory_messages
+        .map(|budget| (budget / queues).max(1));
+    if let Some(share) = queue_share {
+        log!(
+            "Memory budget: {} messages in each of {} queues",
+            share,
+            queues
+        );
+    }
+
+    // Extra sinks, each delivering independently of the output and of each other
+    let mut sinks: Vec<Box<dyn MessageSink>> = Vec::new();
+    if let Some(ref url) = args.post_url {
+        sinks.push(Box::new(PostSink::start(
+            rest::build_client(
+                &args.user_agent,
+                &args.post_headers,
+                args.proxy.as_deref(),
+                rest_timeout,
+                !args.follow_redirects_off,
+                network,
+            )?,
+            PostConfig {
+                url: url.clone(),
+                concurrency: args.post_concurrency,
+                retries: args.post_retries,
+                min_superchat_micros: args.min_superchat_micros,
+                queue_size: queue_share.unwrap_or(post::DEFAULT_QUEUE_SIZE),
+                rate: args.relay_rate,
+            },
+        )));
+    }
+
+    #[cfg(feature = "kafka")]
+    if let (Some(brokers), Some(topic)) = (&args.kafka_brokers, &args.kafka_topic) {
+        sinks.push(Box::new(kafka::KafkaSink::start(kafka::KafkaConfig {
+            brokers: brokers.clone(),
+            topic: topic.clone(),
+            queue_size: queue_share.unwrap_or(kafka::DEFAULT_QUEUE_SIZE),
+        })?));
+    }
+
+    if let Some(ref dir) = args.split_by_type_dir {
+        sinks.push(Box::new(split::SplitSink::start(
+            dir,
+            queue_share.unwrap_or(split::DEFAULT_QUEUE_SIZE),
+        )?));
+    }
+
+    // Open output file if specified (stdout otherwise)
+    let mut output = Output::open(OutputConfig {
+        path: args.output_file.clone(),
+        tee: args.tee,
+        max_bytes: args.max_output_bytes,
+        emit_control_events: args.emit_control_events,
+        emit_member_events: args.emit_member_events,
+        buffer_size: queue_share.unwrap_or(args.output_buffer_size),
+        overflow: args.overflow,
+        flush: FlushPolicy {
+            every: args.flush_every,
+            interval: args.flush_interval.map(tokio::time::Duration::from_millis),
+        },
+        delimiter: args.record_delimiter,
+        format: args.output_format,
+    })?;
+
+    let rest_api_address = rest_api_address();
+
+    // Counts every API call for the quota estimate
+    let api_calls = quota::ApiCalls::default();
+
+    // Metadata from videos.list, if it was looked up
+    let mut stream_info: Option<rest::StreamInfo> = None;
+
+    // Find the live video on the channel; from here on it is treated like --video-id
+    if chat_id.is_none()
+        && let Some(ref channel_id) = args.channel_id
+    {
+        log!("Searching channel {} for live videos...", channel_id);
+        let videos = reconnect::retry_with_backoff(
+            "search for live videos",
+            reconnect::RetryPolicy::startup(args.startup_retries),
+            rest::is_retryable,
+            || async {
+                let access_token = args
+                    .access_token_path
+                    .as_deref()
+                    .map(read_access_token)
+                    .transpose()?;
+                rest::search_live_videos(
+                    &http_client,
+                    &rest_api_address,
+                    channel_id,
+                    api_key.as_deref(),
+                    access_token.as_deref(),
+                    debug_dump.as_ref(),
+                    &api_calls,
+                )
+                .await
+            },
+        )
+        .await?;
+        let video_id =
+            choose_live_video(channel_id, &videos, args.pick.unwrap_or(rest::Pick::Only))?;
+        args.video_id = Some(video_id);
+    }
+
     // If we don't have a chat_id from resume, fetch it using video_id
     if chat_id.is_none() {
         let video_id = args
             .video_id
             .as_ref()
             .ok_or("video-id is required when not resuming or when resume fails to find chat ID")?;
-        eprintln!("Using video ID: {}", video_id);
+        log!("Using video ID: {}", video_id);
 
-        // Get REST API address from environment variable or use default
-        let rest_api_address = std::env::var("REST_API_ADDRESS")
-            .unwrap_or_else(|_| "https://www.googleapis.com".to_string());
-
-        eprintln!("Fetching chat ID from REST API at: {}", rest_api_address);
+        log!("Fetching chat ID from REST API at: {}", rest_api_address);
 
         // Fetch the chat ID from the videos.list endpoint
-        chat_id = Some(fetch_chat_id(&rest_api_address, video_id, api_key.as_deref()).await?);
+        let info = reconnect::retry_with_backoff(
+            "fetch the chat ID",
+            reconnect::RetryPolicy::startup(args.startup_retries),
+            rest::is_retryable,
+            || async {
+                let access_token = args
+                    .access_token_path
+                    .as_deref()
+                    .map(read_access_token)
+                    .transpose()?;
+                rest::fetch_stream_info(
+                    &http_client,
+                    &rest_api_address,
+                    video_id,
+                    api_key.as_deref(),
+                    access_token.as_deref(),
+                    debug_dump.as_ref(),
+                    &api_calls,
+                )
+                .await
+            },
+        )
+        .await
+        .map_err(|e| -> Box<dyn std::error::Error> {
+            if e.is::<rest::VideoNotFound>() {
+                errors::CodedError::new(
+                    "video_not_found",
+                    format!(
+                        "{}; check the video ID (it may be mistyped, or the video may be private or deleted)",
+                        e
+                    ),
+                )
+                .into()
+            } else {
+                e
+            }
+        })?;
+        log!("Got chat ID: {}", info.chat_id);
+        chat_id = Some(info.chat_id.clone());
+        stream_info = Some(info);
+    }
+
+    let mut chat_id = chat_id.expect("chat_id is guaranteed to be Some at this point");
 
-        eprintln!("Got chat ID: {}", chat_id.as_ref().unwrap());
+    let server_url = server_url();
+
+    match args.transport {
+        Transport::Grpc => {
+            if args.proxy.is_some() {
+                log!(
+                    "Warning: --proxy only applies to REST requests; the gRPC stream connects directly (use --transport rest to go through the proxy)"
+                );
+            }
+            log!("Connecting to gRPC server at: {}", server_url);
+        }
+        Transport::Rest => {
+            if args.tls_client_cert.is_some() {
+                log!(
+                    "Warning: --tls-client-cert only applies to the gRPC stream and is ignored with --transport rest"
+                );
+            }
+            log!("Polling REST API at: {}", rest_api_address)
+        }
     }
 
-    let chat_id = chat_id.expect("chat_id is guaranteed to be Some at this point");
+    let mut stream_config = StreamConfig {
+        transport: args.transport,
+        server_url,
+        rest_api_address,
+        api_key,
+        access_token_path: args.access_token_path.clone(),
+        http_client,
+        connect_timeout: tokio::time::Duration::from_secs(args.connect_timeout_secs),
+        network,
+        debug_dump,
+        client_identity,
+        max_message_size: args.max_message_size,
+        api_calls: api_calls.clone(),
+    };
 
-    // Get gRPC server address from environment variable or use default
-    // Note: For TLS-enabled gRPC connections, tonic requires https:// prefix
-    let server_address = std::env::var("SERVER_ADDRESS")
-        .unwrap_or_else(|_| "https://youtube.googleapis.com".to_string());
-    let server_url =
-        if server_address.starts_with("http://") || server_address.starts_with("https://") {
-            server_address
-        } else {
-            // Default to https:// for secure connections
-            format!("https://{}", server_address)
-        };
+    // Open the stream using the retrieved chat ID and page token (if resuming)
+    // (fail fast if the initial connection fails, unless --startup-retries allows retrying)
+    // (a rejected page token isn't retried; with --resume-from, streaming starts over instead)
+    let mut initial_page_token = initial_page_token;
+    let opened = reconnect::retry_with_backoff(
+        "connect",
+        reconnect::RetryPolicy::startup(args.startup_retries),
+        |e| !stream::is_page_token_invalid(e),
+        || stream::open_stream(&stream_config, &chat_id, initial_page_token.clone()),
+    )
+    .await;
+    let mut stream = match opened {
+        Err(e) if stream::is_page_token_invalid(e.as_ref()) && resume_fallback.is_some() => {
+            log!("Failed to connect: {}", e);
+            since = resume_fallback.take();
+            fall_back_to_timestamp(since.expect("checked above"), &mut initial_page_token);
+            reconnect::retry_with_backoff(
+                "connect",
+                reconnect::RetryPolicy::startup(args.startup_retries),
+                |_| true,
+                || stream::open_stream(&stream_config, &chat_id, None),
+            )
+            .await?
+        }
+        opened => opened?,
+    };
 
-    eprintln!("Connecting to gRPC server at: {}", server_url);
+    // Make new archives self-describing
+    if args.write_header && output.starts_empty() {
+        output
+            .write_record(
+                serde_json::json!({
+                    "event": "header",
+                    "tool": env!("CARGO_PKG_NAME"),
+                    "version": VERSION,
+                    "schema_version": SCHEMA_VERSION,
+                    "chat_id": chat_id,
+                    "video_id": args.video_id,
+                    "started_at": humantime::format_rfc3339_seconds(std::time::SystemTime::now())
+                        .to_string(),
+                })
+                .to_string(),
+            )
+            .await?;
+    }
 
-    // Connect to the gRPC server (fail fast if initial connection fails)
-    let mut client = YouTubeClient::connect(server_url.clone(), api_key.clone()).await?;
+    // Mark where this run starts in a resumed archive (skipped when resuming reads it back)
+    if args.resume && !output.starts_empty() {
+        output
+            .write_control_event(serde_json::json!({
+                "event": "resume",
+                "at": humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string(),
+                "from_page_token": initial_page_token,
+            }))
+            .await?;
+    }
 
-    // Stream comments using the retrieved chat ID and page token (if resuming)
-    let mut stream = client
-        .stream_comments(Some(chat_id.clone()), initial_page_token.clone())
-        .await?;
+    // Record what is being captured at the top of this run
+    if args.emit_stream_info {
+        if stream_info.is_none()
+            && let Some(ref video_id) = args.video_id
+        {
+            // Resumed from the file, so videos.list hasn't been called yet
+            match fetch_stream_info(&stream_config, video_id).await {
+                Ok(info) => stream_info = Some(info),
+                Err(e) => log!("Failed to fetch stream info: {}", e),
+            }
+        }
+        output
+            .write_record(
+                serde_json::json!({
+                    "event": "stream_info",
+                    "chat_id": chat_id,
+                    "video_id": args.video_id,
+                    "title": stream_info.as_ref().and_then(|info| info.title.clone()),
+                    "channel_title": stream_info.as_ref().and_then(|info| info.channel_title.clone()),
+                    "concurrent_viewers": stream_info.as_ref().and_then(|info| info.concurrent_viewers),
+                    "fetched_at": humantime::format_rfc3339_seconds(std::time::SystemTime::now())
+                        .to_string(),
+                })
+                .to_string(),
+            )
+            .await?;
+    }
 
-    eprintln!("Reconnect wait time: {} seconds", args.reconnect_wait_secs);
+    log!("Reconnect wait time: {} seconds", args.reconnect_wait_secs);
 
     // Track the next page token for pagination on reconnection
     // Initialize with the resume token if we have one
@@ -315,169 +1794,431 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Track when we should attempt reconnection (None means we're connected)
     let mut reconnect_until: Option<tokio::time::Instant> = None;
 
+    // Set when the stream was lost with --reconnect-wait-secs 0, to reconnect immediately
+    let mut reconnect_now = false;
+
+    // Decides the wait before each reconnect attempt
+    let reconnect_policy = reconnect_policy(&args);
+    let reconnect_wait = tokio::time::Duration::from_secs(args.reconnect_wait_secs);
+    let reconnect_max_wait = tokio::time::Duration::from_secs(args.reconnect_max_wait_secs);
+    let mut reconnector =
+        reconnect::Reconnector::new(reconnect_policy.build(reconnect_wait, reconnect_max_wait))
+            .status_waits(reconnect_wait, reconnect_max_wait);
+
+    // With --once, an empty response is accepted once this has passed
+    let once_deadline =
+        tokio::time::Instant::now() + tokio::time::Duration::from_secs(args.once_wait_secs);
+
+    // Set to the last error if the reconnect policy gave up
+    let mut gave_up: Option<String> = None;
+
+    // Set to the problem found when --strict stopped on a malformed response
+    let mut malformed: Option<String> = None;
+
+    // Why the loop stopped, for the end record (unset when the reconnect policy gave up)
+    let mut end_reason = "";
+
+    // Detects a stream that keeps ending right after each reconnect
+    let mut stream_ends = stream::StreamEnds::new(tokio::time::Duration::from_secs(
+        args.stream_end_cooldown_secs,
+    ));
+
+    // Set when the chat ID should be looked up again before the next reconnect
+    let mut chat_id_stale = false;
+
+    // Counters reported on shutdown
+    let mut stats = Stats::new();
+    if args.superchat_summary {
+        stats.super_chats = Some(Default::default());
+    }
+    stats.api_calls = api_calls;
+    if args.sequence {
+        // Continue numbering from the file being resumed, if it was numbered
+        let last_line = match (&args.output_file, args.resume) {
+            (Some(path), true) => read_last_line(path, args.record_delimiter)?,
+            _ => None,
+        };
+        stats.sequence = Some(
+            last_line
+                .as_deref()
+                .map(sequence::Sequence::resume_after)
+                .unwrap_or_default(),
+        );
+    }
+
+    // Optional periodic quota estimate on stderr
+    let quota_report = args.quota_report_secs.map(tokio::time::Duration::from_secs);
+    let mut quota_interval = tokio::time::interval(quota_report.unwrap_or(status::STATUS_INTERVAL));
+    quota_interval.reset();
+
+    // Optional periodic audience samples in the output
+    let sample_stats = args.sample_stats_secs.map(tokio::time::Duration::from_secs);
+    let mut sample_interval =
+        tokio::time::interval(sample_stats.unwrap_or(status::STATUS_INTERVAL));
+    sample_interval.reset();
+
+    // Optional status file for external supervision
+    let status_file = args.status_file.clone().map(StatusFile::new);
+    let mut status_interval = tokio::time::interval(status::STATUS_INTERVAL);
+
+    // Liveness warning when the stream goes quiet (reset by any response)
+    let idle_warn = args.idle_warn_secs.map(tokio::time::Duration::from_secs);
+    let mut last_activity = tokio::time::Instant::now();
+    let mut idle_deadline = last_activity + idle_warn.unwrap_or_default();
+
+    // In-band liveness record when no chat item has been written for a while
+    let heartbeat = args.heartbeat_secs.map(tokio::time::Duration::from_secs);
+    let mut heartbeat_deadline = tokio::time::Instant::now() + heartbeat.unwrap_or_default();
+
+    let mut filter = ItemFilter {
+        since,
+        only_roles: args.only_roles.clone(),
+        expression: args.filter.clone(),
+        match_words: args.match_words.iter().map(|w| w.to_lowercase()).collect(),
+        match_regex: args.match_regex.clone(),
+        exclude_words: args
+            .exclude_words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect(),
+        threading: if args.top_level_only {
+            Some(filter::Threading::TopLevel)
+        } else if args.threads_only {
+            Some(filter::Threading::Replies)
+        } else {
+            None
+        },
+        sort_by_published: args.sort_by_published,
+        first_seen: (args.mark_first_seen || args.new_chatters_only)
+            .then(|| first_seen_from_archive(&args))
+            .transpose()?,
+        anonymizer: args
+            .anonymize
+            .then(|| anonymize::Anonymizer::new(args.anonymize_salt.as_deref()))
+            .transpose()?,
+        flatten_author: args.flatten_author,
+    };
+
+    // Handle SIGINT everywhere, and SIGTERM/SIGHUP/SIGUSR1 on Unix
+    let mut signals = Signals::new()?;
+
+    let mut paused = args.start_paused;
+    if paused {
+        log!("Output paused; send SIGUSR1 to start writing");
+    }
+
     // Process messages with reconnection on timeout/error and signal handling
-    #[cfg(unix)]
-    {
-        // Unix: Handle both SIGINT and SIGTERM
-        let mut sigterm =
-            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
-
-        loop {
-            // If we're scheduled to reconnect, wait until the time arrives
-            if let Some(until) = reconnect_until {
-                tokio::select! {
-                    // Wait for the reconnection delay
-                    _ = tokio::time::sleep_until(until) => {
-                        // Time to reconnect
-                        reconnect_until = None;
-
-                        attempt_reconnect!(
-                            server_url,
-                            api_key,
-                            chat_id,
-                            next_page_token,
-                            stream,
-                            reconnect_until,
-                            args.reconnect_wait_secs
-                        );
-                    }
-                    // Handle SIGINT (Ctrl+C) - immediate exit even during reconnect wait
-                    _ = tokio::signal::ctrl_c() => {
-                        eprintln!("Received SIGINT, shutting down...");
-                        break;
-                    }
-                    // Handle SIGTERM - immediate exit even during reconnect wait
-                    _ = sigterm.recv() => {
-                        eprintln!("Received SIGTERM, shutting down...");
-                        break;
-                    }
+    loop {
+        // Reconnect straight away instead of waiting on the timer
+        if std::mem::take(&mut reconnect_now) {
+            attempt_reconnect!(
+                stream_config,
+                args.api_key_path,
+                args.video_id,
+                chat_id_stale,
+                chat_id,
+                next_page_token,
+                stream,
+                reconnect_until,
+                reconnector,
+                gave_up,
+                stats
+            );
+        }
+
+        tokio::select! {
+            // Wait for the reconnection delay, if we're scheduled to reconnect
+            _ = tokio::time::sleep_until(reconnect_until.unwrap_or_else(tokio::time::Instant::now)),
+                if reconnect_until.is_some() =>
+            {
+                // Time to reconnect
+                reconnect_until = None;
+
+                attempt_reconnect!(
+                    stream_config,
+                    args.api_key_path,
+                    args.video_id,
+                    chat_id_stale,
+                    chat_id,
+                    next_page_token,
+                    stream,
+                    reconnect_until,
+                    reconnector,
+                    gave_up,
+                    stats
+                );
+            }
+            // Normal operation - handle incoming messages from the stream
+            stream_result = stream.next(), if reconnect_until.is_none() => {
+                if let Some(window) = idle_warn {
+                    last_activity = tokio::time::Instant::now();
+                    idle_deadline = last_activity + window;
                 }
-            } else {
-                // Normal operation - process stream messages
-                tokio::select! {
-                    // Handle incoming messages from the stream
-                    stream_result = stream.next() => {
-                        handle_stream_message!(
-                            stream_result,
-                            next_page_token,
-                            reconnect_until,
-                            args.reconnect_wait_secs,
-                            output_file
-                        );
-                    }
-                    // Handle SIGINT (Ctrl+C)
-                    _ = tokio::signal::ctrl_c() => {
-                        eprintln!("Received SIGINT, shutting down...");
-                        break;
-                    }
-                    // Handle SIGTERM
-                    _ = sigterm.recv() => {
-                        eprintln!("Received SIGTERM, shutting down...");
+                let items_before = stats.items;
+                let responses_before = stats.responses;
+
+                // Check the response's shape before anything is written
+                if let Some(Ok(ref response)) = stream_result
+                    && let Err(problem) = message::validate(response)
+                {
+                    if args.strict {
+                        log!("Malformed response ({}), stopping (--strict)", problem);
+                        end_reason = "malformed_response";
+                        malformed = Some(problem);
                         break;
                     }
+                    log!("Warning: malformed response ({}); writing it anyway", problem);
                 }
-            }
-        }
-    }
 
-    #[cfg(not(unix))]
-    {
-        // Non-Unix (e.g., Windows): Handle only SIGINT/Ctrl+C
-        loop {
-            // If we're scheduled to reconnect, wait until the time arrives
-            if let Some(until) = reconnect_until {
-                tokio::select! {
-                    // Wait for the reconnection delay
-                    _ = tokio::time::sleep_until(until) => {
-                        // Time to reconnect
-                        reconnect_until = None;
-
-                        attempt_reconnect!(
-                            server_url,
-                            api_key,
-                            chat_id,
-                            next_page_token,
-                            stream,
-                            reconnect_until,
-                            args.reconnect_wait_secs
-                        );
-                    }
-                    // Handle SIGINT (Ctrl+C) - immediate exit even during reconnect wait
-                    _ = tokio::signal::ctrl_c() => {
-                        eprintln!("Received SIGINT, shutting down...");
-                        break;
-                    }
+                handle_stream_message!(
+                    stream_result,
+                    next_page_token,
+                    reconnect_until,
+                    reconnect_now,
+                    reconnector,
+                    gave_up,
+                    stream_ends,
+                    output,
+                    stats,
+                    filter,
+                    sinks,
+                    paused,
+                    resume_fallback
+                );
+
+                if let Some(interval) = heartbeat
+                    && stats.items != items_before
+                {
+                    heartbeat_deadline = tokio::time::Instant::now() + interval;
                 }
-            } else {
-                // Normal operation - process stream messages
-                tokio::select! {
-                    // Handle incoming messages from the stream
-                    stream_result = stream.next() => {
-                        handle_stream_message!(
-                            stream_result,
-                            next_page_token,
-                            reconnect_until,
-                            args.reconnect_wait_secs,
-                            output_file
-                        );
+
+                if args.once
+                    && (stats.items != items_before
+                        || (stats.responses != responses_before
+                            && tokio::time::Instant::now() >= once_deadline))
+                {
+                    log!("Got a response, exiting (--once)");
+                    end_reason = "once";
+                    break;
+                }
+            }
+            // Tell consumers the fetcher is alive while no chat items are being written
+            _ = tokio::time::sleep_until(heartbeat_deadline), if heartbeat.is_some() => {
+                if !paused {
+                    output
+                        .write_record(
+                            serde_json::json!({
+                                "event": "heartbeat",
+                                "ts": humantime::format_rfc3339_seconds(std::time::SystemTime::now())
+                                    .to_string(),
+                                "connected": reconnect_until.is_none(),
+                            })
+                            .to_string(),
+                        )
+                        .await?;
+                }
+                heartbeat_deadline = tokio::time::Instant::now() + heartbeat.unwrap_or_default();
+            }
+            // Warn when nothing has arrived within the idle window
+            _ = tokio::time::sleep_until(idle_deadline),
+                if idle_warn.is_some() && reconnect_until.is_none() =>
+            {
+                log!(
+                    "Warning: no messages received for {} seconds",
+                    last_activity.elapsed().as_secs()
+                );
+                idle_deadline = tokio::time::Instant::now() + idle_warn.unwrap_or_default();
+            }
+            // Periodically refresh the status file
+            _ = status_interval.tick(), if status_file.is_some() => {
+                if let Some(ref status_file) = status_file {
+                    if let Err(e) = status_file.write(reconnect_until.is_none(), &chat_id, &stats) {
+                        log!("Failed to write status file: {}", e);
                     }
-                    // Handle SIGINT (Ctrl+C)
-                    _ = tokio::signal::ctrl_c() => {
-                        eprintln!("Received SIGINT, shutting down...");
-                        break;
+                }
+            }
+            // Periodically report the API calls made and estimated quota used
+            _ = quota_interval.tick(), if quota_report.is_some() => {
+                log!("API usage so far: {}", stats.api_calls.summary());
+            }
+            // Sample the audience size for correlating with chat activity
+            _ = sample_interval.tick(), if sample_stats.is_some() && !paused => {
+                if let Some(ref video_id) = args.video_id {
+                    match fetch_stream_info(&stream_config, video_id).await {
+                        Ok(info) => {
+                            output
+                                .write_record(
+                                    serde_json::json!({
+                                        "event": "stats",
+                                        "concurrent_viewers": info.concurrent_viewers,
+                                        "likes": info.likes,
+                                        "ts": humantime::format_rfc3339_seconds(std::time::SystemTime::now())
+                                            .to_string(),
+                                    })
+                                    .to_string(),
+                                )
+                                .await?;
+                        }
+                        Err(e) => log!("Failed to sample stream stats: {}", e),
                     }
                 }
             }
+            // Handle SIGHUP - reload credentials and chat ID, then reconnect immediately
+            _ = signals.sighup.recv() => {
+                log!("Received SIGHUP, reloading and reconnecting...");
+                chat_id_stale = true;
+                reconnect_until = Some(tokio::time::Instant::now());
+            }
+            // Stop once the output file has reached --max-output-bytes, exactly like SIGTERM
+            _ = output.limit_reached(), if args.max_output_bytes.is_some() => {
+                log!("Reached --max-output-bytes, shutting down...");
+                end_reason = "max_output_bytes";
+                break;
+            }
+            // Stop once --max-duration has elapsed, exactly like SIGTERM
+            _ = tokio::time::sleep_until(stop_at.unwrap_or_else(tokio::time::Instant::now)),
+                if stop_at.is_some() =>
+            {
+                log!("Reached --max-duration, shutting down...");
+                end_reason = "max_duration";
+                break;
+            }
+            // Handle SIGUSR1 - toggle pausing output
+            _ = signals.sigusr1.recv() => {
+                paused = !paused;
+                log!(
+                    "Received SIGUSR1, output {}",
+                    if paused { "paused" } else { "resumed" }
+                );
+            }
+            // Handle SIGINT (Ctrl+C) - immediate exit even during reconnect wait
+            _ = tokio::signal::ctrl_c() => {
+                log!("Received SIGINT, shutting down...");
+                end_reason = "sigint";
+                break;
+            }
+            // Handle SIGTERM - immediate exit even during reconnect wait
+            _ = signals.sigterm.recv() => {
+                log!("Received SIGTERM, shutting down...");
+                end_reason = "sigterm";
+                break;
+            }
         }
     }
 
-    eprintln!("Shutdown complete");
-    Ok(())
-}
-
-async fn fetch_chat_id(
-    rest_api_address: &str,
-    video_id: &str,
-    api_key: Option<&str>,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let mut url = format!(
-        "{}/youtube/v3/videos?part=liveStreamingDetails&id={}",
-        rest_api_address, video_id
-    );
-
-    // Add API key as query parameter if provided
-    if let Some(key) = api_key {
-        url.push_str(&format!("&key={}", key));
+    // Stop accepting new messages, but write any that had already arrived when the signal won
+    if reconnect_until.is_none() && malformed.is_none() {
+        while let Ok(Some(Ok(message))) =
+            tokio::time::timeout(tokio::time::Duration::ZERO, stream.next()).await
+        {
+            if args.strict && message::validate(&message).is_err() {
+                break;
+            }
+            process_response(
+                message,
+                &mut next_page_token,
+                &mut output,
+                &mut stats,
+                &mut filter,
+                &mut sinks,
+                paused,
+            )
+            .await?;
+        }
     }
 
-    let client = reqwest::Client::new();
-    let response = client.get(&url).send().await?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await?;
-        return Err(format!("Failed to fetch video data (status {}): {}", status, body).into());
+    // Mark a graceful end, so consumers can tell it from a crash (which leaves no end record)
+    if let Some(ref error) = gave_up {
+        end_reason = if reconnector.credentials_rejected() {
+            "unauthorized"
+        } else if *error == reconnect::StreamEnded.to_string() {
+            "stream_ended"
+        } else {
+            "reconnect_gave_up"
+        };
+    }
+    if let Err(e) = output
+        .write_control_event(serde_json::json!({
+            "event": "end",
+            "reason": end_reason,
+            "messages": stats.items,
+        }))
+        .await
+    {
+        log!("Failed to write end record: {}", e);
     }
 
-    let body: serde_json::Value = response.json().await?;
-
-    // Extract the activeLiveChatId from the response
-    let items = body.get("items").ok_or("Response missing 'items' field")?;
+    // Give the output a bounded window to flush and sync before exiting
+    output
+        .shutdown(tokio::time::Duration::from_secs(args.shutdown_grace_secs))
+        .await;
+    sink::shutdown_all(
+        sinks,
+        tokio::time::Duration::from_secs(args.shutdown_grace_secs),
+    )
+    .await;
 
-    let items_array = items.as_array().ok_or("'items' field is not an array")?;
+    if let Some(ref status_file) = status_file {
+        status_file.remove();
+    }
 
-    let first_item = items_array
-        .first()
-        .ok_or("No video found with the given ID")?;
+    stats.print_summary();
+    log!("Shutdown complete");
+    if let Some(problem) = malformed {
+        return Err(errors::CodedError::new(
+            "malformed_response",
+            format!("Malformed response: {}", problem),
+        )
+        .into());
+    }
+    match gave_up {
+        Some(error) if reconnector.credentials_rejected() => Err(errors::CodedError::new(
+            "unauthorized",
+            format!(
+                "Re-authorization required: the credentials were rejected again after re-reading them ({}). Refresh the token in --access-token-path or check the API key.",
+                error
+            ),
+        )
+        .into()),
+        Some(error) if args.once => Err(errors::CodedError::new(
+            "stream_closed",
+            format!(
+                "The stream closed before a response with messages arrived: {}",
+                error
+            ),
+        )
+        .into()),
+        Some(error) => Err(errors::CodedError::new(
+            "reconnect_gave_up",
+            format!("Gave up reconnecting after: {}", error),
+        )
+        .into()),
+        None => Ok(()),
+    }
+}
 
-    let live_streaming_details = first_item
-        .get("liveStreamingDetails")
-        .ok_or("Video does not have live streaming details (not a live video)")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let chat_id = live_streaming_details
-        .get("activeLiveChatId")
-        .and_then(|id| id.as_str())
-        .ok_or("No active live chat ID found (stream may not be active)")?;
+    fn validate(args: &[&str]) -> Result<(), String> {
+        let args =
+            Args::try_parse_from(std::iter::once("yt-comment-fetcher").chain(args.iter().copied()))
+                .map_err(|e| e.to_string())?;
+        validate_args(&args).map_err(|e| e.to_string())
+    }
 
-    Ok(chat_id.to_string())
+    #[test]
+    fn flatten_author_conflicts_with_protobuf_output() {
+        let error = validate(&[
+            "--video-id",
+            "v",
+            "--flatten-author",
+            "--output-format",
+            "protobuf",
+        ])
+        .unwrap_err();
+        assert!(error.contains("--flatten-author"), "{}", error);
+        assert!(validate(&["--video-id", "v", "--flatten-author"]).is_ok());
+    }
 }
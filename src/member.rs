@@ -0,0 +1,37 @@
+use crate::message::{Item, field, str_field};
+use serde_json::{Value, json};
+
+/// Compact record for a new member or membership milestone item (`--emit-member-events`),
+/// or None for other item types
+pub fn member_event(item: Item) -> Option<Value> {
+    let snippet = item.snippet()?;
+    let (event, details) = match item.message_type()?.as_str() {
+        "newSponsorEvent" => ("new_sponsor", field(snippet, "new_sponsor_details")),
+        "memberMilestoneChatEvent" => (
+            "member_milestone",
+            field(snippet, "member_milestone_chat_details"),
+        ),
+        _ => return None,
+    };
+
+    // REST encodes member_month as a number, but accept a string too
+    let months = details
+        .and_then(|details| field(details, "member_month"))
+        .and_then(|months| match months {
+            Value::String(s) => s.parse().ok(),
+            other => other.as_u64(),
+        });
+    let message = details
+        .and_then(|details| str_field(details, "user_comment"))
+        .or_else(|| item.display_message());
+
+    Some(json!({
+        "event": event,
+        "channel_id": item.author_channel_id(),
+        "author_name": item.author_name(),
+        "member_level_name": details.and_then(|details| str_field(details, "member_level_name")),
+        "months": months,
+        "message": message,
+        "published_at": item.published_at(),
+    }))
+}
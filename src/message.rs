@@ -0,0 +1,242 @@
+//! Accessors over chat responses in their JSON form.
+//!
+//! Responses are handled as `serde_json::Value` so the gRPC and REST transports share one
+//! shape. Archives written by this tool use snake_case field names; camelCase names (as
+//! returned by the REST API) are accepted too.
+
+use serde_json::Value;
+
+/// Look up a field by its snake_case name, falling back to the camelCase spelling
+pub fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    value
+        .get(name)
+        .or_else(|| value.get(to_camel_case(name).as_str()))
+}
+
+/// Look up a string field (see `field`)
+pub fn str_field<'a>(value: &'a Value, name: &str) -> Option<&'a str> {
+    field(value, name).and_then(|v| v.as_str())
+}
+
+/// The chat items of a response (empty if the response has none)
+pub fn items(response: &Value) -> &[Value] {
+    field(response, "items")
+        .and_then(|items| items.as_array())
+        .map_or(&[], |items| items.as_slice())
+}
+
+/// Check that a response has the shape the API documents: an `items` array (if present)
+/// whose items each have an ID and a snippet with a type. Returns the first problem found.
+pub fn validate(response: &Value) -> Result<(), String> {
+    if !response.is_object() {
+        return Err("response is not a JSON object".to_string());
+    }
+    let items = match field(response, "items") {
+        None | Some(Value::Null) => return Ok(()),
+        Some(Value::Array(items)) => items,
+        Some(_) => return Err("'items' is not an array".to_string()),
+    };
+    for (index, item) in items.iter().enumerate() {
+        let Some(id) = str_field(item, "id").filter(|id| !id.is_empty()) else {
+            return Err(format!("item {} has no id", index));
+        };
+        let has_type = Item(item)
+            .snippet()
+            .and_then(|snippet| field(snippet, "type"))
+            .is_some_and(|kind| !kind.is_null());
+        if !has_type {
+            return Err(format!("item {} ({}) has no snippet type", index, id));
+        }
+    }
+    Ok(())
+}
+
+/// An author's most significant chat role, from highest to lowest precedence
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Owner,
+    Moderator,
+    Sponsor,
+    Verified,
+    Viewer,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Moderator => "moderator",
+            Role::Sponsor => "sponsor",
+            Role::Verified => "verified",
+            Role::Viewer => "viewer",
+        }
+    }
+}
+
+/// Read-only view over one chat item (`LiveChatMessage`)
+#[derive(Clone, Copy)]
+pub struct Item<'a>(pub &'a Value);
+
+impl<'a> Item<'a> {
+    pub fn snippet(&self) -> Option<&'a Value> {
+        field(self.0, "snippet")
+    }
+
+    pub fn author_details(&self) -> Option<&'a Value> {
+        field(self.0, "author_details")
+    }
+
+    /// The message type as a REST-style name such as `textMessageEvent`.
+    /// gRPC responses carry the type as an enum number, which is mapped to the same name.
+    pub fn message_type(&self) -> Option<String> {
+        match field(self.snippet()?, "type")? {
+            Value::String(name) => Some(name.clone()),
+            Value::Number(code) => type_name(code.as_i64()?).map(|name| name.to_string()),
+            _ => None,
+        }
+    }
+
+    pub fn published_at(&self) -> Option<&'a str> {
+        str_field(self.snippet()?, "published_at")
+    }
+
+    pub fn display_message(&self) -> Option<&'a str> {
+        str_field(self.snippet()?, "display_message")
+    }
+
+    /// The author's display name (also read from `author_name` in `--flatten-author` archives)
+    pub fn author_name(&self) -> Option<&'a str> {
+        match self.author_details() {
+            Some(author) => str_field(author, "display_name"),
+            None => str_field(self.0, "author_name"),
+        }
+    }
+
+    /// The author's role derived from the `is_chat_*`/`is_verified` flags
+    pub fn role(&self) -> Role {
+        let flag = |name: &str| {
+            match self.author_details() {
+                Some(author) => field(author, name),
+                None => self.0.get(format!("author_{}", name).as_str()),
+            }
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        };
+        if flag("is_chat_owner") {
+            Role::Owner
+        } else if flag("is_chat_moderator") {
+            Role::Moderator
+        } else if flag("is_chat_sponsor") {
+            Role::Sponsor
+        } else if flag("is_verified") {
+            Role::Verified
+        } else {
+            Role::Viewer
+        }
+    }
+
+    pub fn author_channel_id(&self) -> Option<&'a str> {
+        self.author_details()
+            .and_then(|author| str_field(author, "channel_id"))
+            .or_else(|| str_field(self.0, "author_channel_id"))
+            .or_else(|| str_field(self.snippet()?, "author_channel_id"))
+    }
+
+    /// The super chat or super sticker details, if this is a paid message
+    pub fn paid_details(&self) -> Option<&'a Value> {
+        let snippet = self.snippet()?;
+        field(snippet, "super_chat_details").or_else(|| field(snippet, "super_sticker_details"))
+    }
+
+    /// Paid amount in micros of the currency (REST encodes this as a string)
+    pub fn amount_micros(&self) -> Option<u64> {
+        match field(self.paid_details()?, "amount_micros")? {
+            Value::Number(n) => n.as_u64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn currency(&self) -> Option<&'a str> {
+        str_field(self.paid_details()?, "currency")
+    }
+
+    /// ID of the earlier message this item refers to: the gifting message for a received gift
+    /// membership, or the message a deletion or retraction applies to
+    pub fn related_message_id(&self) -> Option<&'a str> {
+        let snippet = self.snippet()?;
+        [
+            (
+                "gift_membership_received_details",
+                "associated_membership_gifting_message_id",
+            ),
+            ("message_deleted_details", "deleted_message_id"),
+            ("message_retracted_details", "retracted_message_id"),
+        ]
+        .into_iter()
+        .find_map(|(details, id)| str_field(field(snippet, details)?, id))
+    }
+}
+
+/// Format an amount in micros as a decimal string without rounding (e.g. 5500000 -> "5.5")
+pub fn format_micros(micros: u64) -> String {
+    let whole = micros / 1_000_000;
+    let fraction = micros % 1_000_000;
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:06}", fraction);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// The gRPC `LiveChatMessageSnippet.TypeWrapper.Type` enum numbers and their REST names
+const TYPES: [(i64, &str); 16] = [
+    (1, "textMessageEvent"),
+    (2, "tombstone"),
+    (3, "fanFundingEvent"),
+    (4, "chatEndedEvent"),
+    (5, "sponsorOnlyModeStartedEvent"),
+    (6, "sponsorOnlyModeEndedEvent"),
+    (7, "newSponsorEvent"),
+    (8, "messageDeletedEvent"),
+    (9, "messageRetractedEvent"),
+    (10, "userBannedEvent"),
+    (15, "superChatEvent"),
+    (16, "superStickerEvent"),
+    (17, "memberMilestoneChatEvent"),
+    (18, "membershipGiftingEvent"),
+    (19, "giftMembershipReceivedEvent"),
+    (20, "pollEvent"),
+];
+
+/// Map the gRPC message type enum number to its REST name
+fn type_name(code: i64) -> Option<&'static str> {
+    TYPES
+        .iter()
+        .find(|(number, _)| *number == code)
+        .map(|(_, name)| *name)
+}
+
+/// Map a REST message type name to the gRPC enum number
+pub fn type_code(name: &str) -> Option<i64> {
+    TYPES
+        .iter()
+        .find(|(_, type_name)| *type_name == name)
+        .map(|(number, _)| *number)
+}
+
+fn to_camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
@@ -0,0 +1,438 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Stdout, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Notify, mpsc};
+use tokio::time::Instant;
+
+/// What to do with a record when the writer can't keep up and the buffer is full
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the record and count it as dropped
+    Drop,
+    /// Wait for the writer to catch up (pauses reading from the server)
+    Block,
+}
+
+/// What separates records in the output
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecordDelimiter {
+    /// Newline-delimited JSON
+    #[default]
+    Newline,
+    /// NUL-delimited JSON (for `xargs -0` and similar tools)
+    Nul,
+}
+
+impl RecordDelimiter {
+    pub fn byte(self) -> u8 {
+        match self {
+            RecordDelimiter::Newline => b'\n',
+            RecordDelimiter::Nul => b'\0',
+        }
+    }
+}
+
+/// Reader that presents a record delimiter as a newline
+pub struct DelimiterAsNewline<R> {
+    inner: R,
+    delimiter: u8,
+}
+
+impl<R> DelimiterAsNewline<R> {
+    pub fn new(inner: R, delimiter: RecordDelimiter) -> Self {
+        DelimiterAsNewline {
+            inner,
+            delimiter: delimiter.byte(),
+        }
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for DelimiterAsNewline<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for byte in &mut buf[..n] {
+            if *byte == self.delimiter {
+                *byte = b'\n';
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl<R: std::io::Seek> std::io::Seek for DelimiterAsNewline<R> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// How chat responses are encoded in the output
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One JSON record per response, separated by the record delimiter
+    #[default]
+    Json,
+    /// Length-delimited `LiveChatMessageListResponse` protobuf frames (not line-based)
+    Protobuf,
+}
+
+/// When buffered records are flushed to the destination.
+///
+/// With neither limit set every record is flushed as soon as it is written. Otherwise records
+/// are flushed once `every` of them are pending, or once the oldest pending record is
+/// `interval` old, whichever comes first.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FlushPolicy {
+    pub every: Option<u64>,
+    pub interval: Option<Duration>,
+}
+
+impl FlushPolicy {
+    /// Whether `pending` unflushed records should be flushed right away
+    fn due(&self, pending: u64) -> bool {
+        match (self.every, self.interval) {
+            (Some(every), _) => pending >= every,
+            (None, Some(_)) => false,
+            (None, None) => true,
+        }
+    }
+}
+
+/// Output settings taken from the command line
+pub struct OutputConfig {
+    /// Output file path (stdout when None)
+    pub path: Option<String>,
+    /// Also write every record to stdout when writing to a file
+    pub tee: bool,
+    /// Signal [`Output::limit_reached`] once the output file is at least this many bytes
+    pub max_bytes: Option<u64>,
+    /// Whether control records (reconnects etc.) are written alongside chat messages
+    pub emit_control_events: bool,
+    /// Whether a compact record is written after each response for every membership event in it
+    pub emit_member_events: bool,
+    /// Number of records buffered between the read loop and the writer
+    pub buffer_size: usize,
+    pub overflow: OverflowPolicy,
+    pub flush: FlushPolicy,
+    pub delimiter: RecordDelimiter,
+    pub format: OutputFormat,
+}
+
+/// Destination for JSON records: the output file if configured (optionally mirrored to
+/// stdout), otherwise stdout.
+///
+/// Records are handed to a dedicated blocking writer through a bounded channel, so a slow
+/// consumer on the other end of a pipe never stalls the network read loop.
+pub struct Output {
+    /// Whether nothing has been written to the destination yet (new/empty file or stdout)
+    starts_empty: bool,
+    tx: mpsc::Sender<Vec<u8>>,
+    writer: tokio::task::JoinHandle<()>,
+    /// Notified by the writer when the file reaches `max_bytes`
+    limit_reached: Arc<Notify>,
+    overflow: OverflowPolicy,
+    delimiter: u8,
+    format: OutputFormat,
+    emit_control_events: bool,
+    emit_member_events: bool,
+    /// Records discarded because the buffer was full
+    dropped: u64,
+}
+
+impl Output {
+    /// Open the output file in append mode (or use stdout) and start the writer
+    pub fn open(config: OutputConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let destination = match config.path {
+            Some(ref path) => {
+                log!("Output file: {}", path);
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Failed to open output file '{}': {}", path, e))?;
+                if config.tee {
+                    log!("Mirroring output to stdout");
+                    Destination::Tee {
+                        file: BufWriter::new(file),
+                        stdout: Some(BufWriter::new(std::io::stdout())),
+                    }
+                } else {
+                    Destination::File(BufWriter::new(file))
+                }
+            }
+            None => Destination::Stdout(BufWriter::new(std::io::stdout())),
+        };
+
+        let size = match destination {
+            Destination::File(ref file) | Destination::Tee { ref file, .. } => {
+                file.get_ref().metadata()?.len()
+            }
+            Destination::Stdout(_) => 0,
+        };
+        let starts_empty = size == 0;
+
+        let (tx, rx) = mpsc::channel(config.buffer_size.max(1));
+        let flush = config.flush;
+        let limit_reached = Arc::new(Notify::new());
+        let limit = config
+            .max_bytes
+            .filter(|_| config.path.is_some())
+            .map(|max_bytes| {
+                let mut limit = SizeLimit {
+                    size: 0,
+                    max_bytes,
+                    reached: limit_reached.clone(),
+                };
+                // A file that is already full stops the run straight away
+                limit.record_written(size);
+                limit
+            });
+        let writer = tokio::task::spawn_blocking(move || run_writer(destination, rx, flush, limit));
+
+        Ok(Output {
+            starts_empty,
+            tx,
+            writer,
+            limit_reached,
+            overflow: config.overflow,
+            delimiter: config.delimiter.byte(),
+            format: config.format,
+            emit_control_events: config.emit_control_events,
+            emit_member_events: config.emit_member_events,
+            dropped: 0,
+        })
+    }
+
+    /// Queue one JSON record to be written followed by the record delimiter
+    pub async fn write_record(&mut self, json: String) -> std::io::Result<()> {
+        let mut record = json.into_bytes();
+        record.push(self.delimiter);
+        self.write_bytes(record).await
+    }
+
+    /// Queue a chat response in the configured output format
+    pub async fn write_response(
+        &mut self,
+        response: &serde_json::Value,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self.format {
+            OutputFormat::Json => self.write_record(serde_json::to_string(response)?).await?,
+            OutputFormat::Protobuf => self.write_bytes(crate::protobuf::encode(response)?).await?,
+        }
+        Ok(())
+    }
+
+    /// Queue bytes to be written as they are
+    async fn write_bytes(&mut self, bytes: Vec<u8>) -> std::io::Result<()> {
+        let closed = || std::io::Error::other("output writer has stopped");
+
+        match self.overflow {
+            OverflowPolicy::Block => self.tx.send(bytes).await.map_err(|_| closed()),
+            OverflowPolicy::Drop => match self.tx.try_send(bytes) {
+                Ok(()) => Ok(()),
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    self.dropped += 1;
+                    Ok(())
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => Err(closed()),
+            },
+        }
+    }
+
+    /// Whether the destination was empty when opened, i.e. the next record is the first line
+    pub fn starts_empty(&self) -> bool {
+        self.starts_empty
+    }
+
+    /// Write a control record such as `{"event":"reconnect",...}` if control events are enabled
+    pub async fn write_control_event(&mut self, event: serde_json::Value) -> std::io::Result<()> {
+        if !self.emit_control_events {
+            return Ok(());
+        }
+        self.write_record(event.to_string()).await
+    }
+
+    /// Write a compact record for each new member or milestone item in the response, if
+    /// member events are enabled
+    pub async fn write_member_events(
+        &mut self,
+        response: &serde_json::Value,
+    ) -> std::io::Result<()> {
+        if !self.emit_member_events {
+            return Ok(());
+        }
+        for record in crate::message::items(response)
+            .iter()
+            .filter_map(|item| crate::member::member_event(crate::message::Item(item)))
+        {
+            self.write_record(record.to_string()).await?;
+        }
+        Ok(())
+    }
+
+    /// Resolves once the output file has reached `max_bytes` (never without a limit)
+    pub async fn limit_reached(&self) {
+        self.limit_reached.notified().await
+    }
+
+    /// Number of records dropped because the writer couldn't keep up
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Let the writer finish queued records, then flush and sync, giving up once the grace
+    /// period has elapsed
+    pub async fn shutdown(self, grace: Duration) {
+        drop(self.tx);
+        match tokio::time::timeout(grace, self.writer).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log!("Output writer failed: {}", e),
+            Err(_) => log!(
+                "Output was not flushed within {} seconds, giving up",
+                grace.as_secs()
+            ),
+        }
+    }
+}
+
+enum Destination {
+    File(BufWriter<File>),
+    Stdout(BufWriter<Stdout>),
+    /// A file mirrored to stdout (`--tee`). The file is the archive, so a failure on stdout
+    /// (e.g. the viewer was closed) only stops the mirroring.
+    Tee {
+        file: BufWriter<File>,
+        stdout: Option<BufWriter<Stdout>>,
+    },
+}
+
+impl Destination {
+    fn write_record(&mut self, record: &[u8]) -> std::io::Result<()> {
+        match self {
+            Destination::File(file) => file.write_all(record),
+            Destination::Stdout(stdout) => stdout.write_all(record),
+            Destination::Tee { file, stdout } => {
+                file.write_all(record)?;
+                mirror(stdout, |stdout| stdout.write_all(record));
+                Ok(())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Destination::File(file) => file.flush(),
+            Destination::Stdout(stdout) => stdout.flush(),
+            Destination::Tee { file, stdout } => {
+                file.flush()?;
+                mirror(stdout, |stdout| stdout.flush());
+                Ok(())
+            }
+        }
+    }
+
+    /// Flush, and for files also sync to disk
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        match self {
+            Destination::File(file) | Destination::Tee { file, .. } => file.get_ref().sync_all(),
+            Destination::Stdout(_) => Ok(()),
+        }
+    }
+}
+
+/// Apply a write to the stdout mirror, dropping the mirror if it fails
+fn mirror(
+    stdout: &mut Option<BufWriter<Stdout>>,
+    write: impl FnOnce(&mut BufWriter<Stdout>) -> std::io::Result<()>,
+) {
+    if let Some(out) = stdout
+        && let Err(e) = write(out)
+    {
+        log!("Stopped mirroring output to stdout: {}", e);
+        *stdout = None;
+    }
+}
+
+/// Tracks the size of the output file against `--max-output-bytes`
+struct SizeLimit {
+    /// Bytes in the file so far, including any written before this run
+    size: u64,
+    max_bytes: u64,
+    reached: Arc<Notify>,
+}
+
+impl SizeLimit {
+    /// Count a written record, notifying the read loop the first time the limit is reached
+    fn record_written(&mut self, len: u64) {
+        let before = self.size;
+        self.size += len;
+        if before < self.max_bytes && self.size >= self.max_bytes {
+            log!(
+                "Output file reached {} bytes (--max-output-bytes {})",
+                self.size,
+                self.max_bytes
+            );
+            self.reached.notify_one();
+        }
+    }
+}
+
+/// Write queued records until the channel closes, then flush and sync whatever is left
+fn run_writer(
+    mut destination: Destination,
+    mut rx: mpsc::Receiver<Vec<u8>>,
+    policy: FlushPolicy,
+    mut limit: Option<SizeLimit>,
+) {
+    let runtime = tokio::runtime::Handle::current();
+    let mut pending = 0u64;
+    // Time by which pending records must be flushed (interval policy only)
+    let mut flush_deadline: Option<Instant> = None;
+
+    loop {
+        let record = match flush_deadline {
+            Some(deadline) => {
+                match runtime.block_on(tokio::time::timeout_at(deadline, rx.recv())) {
+                    Ok(record) => record,
+                    Err(_) => {
+                        if let Err(e) = destination.flush() {
+                            log!("Failed to write output: {}", e);
+                            return;
+                        }
+                        pending = 0;
+                        flush_deadline = None;
+                        continue;
+                    }
+                }
+            }
+            None => rx.blocking_recv(),
+        };
+        let Some(record) = record else {
+            break;
+        };
+
+        let mut result = destination.write_record(&record);
+        if result.is_ok()
+            && let Some(ref mut limit) = limit
+        {
+            limit.record_written(record.len() as u64);
+        }
+        pending += 1;
+        if result.is_ok() && policy.due(pending) {
+            result = destination.flush();
+            pending = 0;
+            flush_deadline = None;
+        } else if flush_deadline.is_none() {
+            flush_deadline = policy.interval.map(|interval| Instant::now() + interval);
+        }
+        if let Err(e) = result {
+            log!("Failed to write output: {}", e);
+            return;
+        }
+    }
+
+    if let Err(e) = destination.finish() {
+        log!("Failed to flush output on shutdown: {}", e);
+    }
+}
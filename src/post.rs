@@ -0,0 +1,249 @@
+use crate::message::{self, Item};
+use crate::sink::MessageSink;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+
+/// Number of items queued for delivery before new ones are dropped, by default
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+/// Delay before the first retry of a failed POST, doubled on each further attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Webhook settings taken from the command line
+pub struct PostConfig {
+    pub url: String,
+    /// Maximum number of requests in flight at once
+    pub concurrency: usize,
+    /// Extra attempts after a failed delivery (0 disables retries)
+    pub retries: u32,
+    /// Only deliver paid messages worth at least this many micros (everything when None)
+    pub min_superchat_micros: Option<u64>,
+    /// Number of items queued for delivery before new ones are dropped
+    pub queue_size: usize,
+    /// Most POSTs started per second, with bursts of up to one second's worth (unlimited when
+    /// None)
+    pub rate: Option<f64>,
+}
+
+/// Delivers each chat item as a JSON POST to a webhook.
+///
+/// Items are queued and sent by a background task, so a slow, failing or rate-limited endpoint
+/// never stalls the stream. When the queue is full, new items are dropped and counted.
+pub struct PostSink {
+    tx: mpsc::Sender<Value>,
+    task: tokio::task::JoinHandle<()>,
+    min_superchat_micros: Option<u64>,
+    dropped: u64,
+}
+
+impl PostSink {
+    pub fn start(client: reqwest::Client, config: PostConfig) -> Self {
+        log!("Posting messages to: {}", config.url);
+        if let Some(min) = config.min_superchat_micros {
+            log!(
+                "Only posting paid messages worth at least {} in their currency",
+                message::format_micros(min)
+            );
+        }
+        if let Some(rate) = config.rate {
+            log!("Limiting webhook POSTs to {} per second", rate);
+        }
+        let (tx, rx) = mpsc::channel(config.queue_size.max(1));
+        let min_superchat_micros = config.min_superchat_micros;
+        let task = tokio::spawn(run(client, config, rx));
+        PostSink {
+            tx,
+            task,
+            min_superchat_micros,
+            dropped: 0,
+        }
+    }
+}
+
+impl MessageSink for PostSink {
+    /// Queue each item of the response for delivery (only paid ones above the threshold, if set)
+    fn offer(&mut self, response: &Value) {
+        for item in message::items(response) {
+            if let Some(min) = self.min_superchat_micros
+                && Item(item).amount_micros().is_none_or(|amount| amount < min)
+            {
+                continue;
+            }
+            if self.tx.try_send(item.clone()).is_err() {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Finish delivering queued items, giving up once the grace period has elapsed
+    fn shutdown(self: Box<Self>, grace: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let PostSink {
+            tx, task, dropped, ..
+        } = *self;
+        Box::pin(async move {
+            drop(tx);
+            if dropped > 0 {
+                log!("Webhook: {} items dropped (queue full)", dropped);
+            }
+            if tokio::time::timeout(grace, task).await.is_err() {
+                log!(
+                    "Webhook deliveries did not finish within {} seconds, giving up",
+                    grace.as_secs()
+                );
+            }
+        })
+    }
+}
+
+async fn run(client: reqwest::Client, config: PostConfig, mut rx: mpsc::Receiver<Value>) {
+    let url = Arc::new(config.url);
+    let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+    let mut deliveries = JoinSet::new();
+    let mut delivered = 0u64;
+    let mut failed = 0u64;
+    let mut bucket = config.rate.map(TokenBucket::new);
+    let mut count = |ok: bool| {
+        if ok {
+            delivered += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    while let Some(item) = rx.recv().await {
+        if let Some(ref mut bucket) = bucket {
+            while let Some(wait) = bucket.take(Instant::now()) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+        let permit = permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        let client = client.clone();
+        let url = url.clone();
+        deliveries.spawn(async move {
+            let _permit = permit;
+            deliver(&client, &url, &item, config.retries).await
+        });
+
+        while let Some(result) = deliveries.try_join_next() {
+            count(result.unwrap_or(false));
+        }
+    }
+
+    while let Some(result) = deliveries.join_next().await {
+        count(result.unwrap_or(false));
+    }
+    log!("Webhook: {} delivered, {} failed", delivered, failed);
+}
+
+/// Token bucket refilled at `rate` tokens per second, holding up to one second's worth
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        let capacity = rate.max(1.0);
+        TokenBucket {
+            rate,
+            capacity,
+            tokens: capacity,
+            refilled_at: None,
+        }
+    }
+
+    /// Take a token, or return how long to wait before one is available
+    fn take(&mut self, now: Instant) -> Option<Duration> {
+        if let Some(refilled_at) = self.refilled_at {
+            let elapsed = now.saturating_duration_since(refilled_at).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        }
+        self.refilled_at = Some(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+}
+
+/// POST one item, retrying with exponential backoff. Returns whether it was delivered.
+async fn deliver(client: &reqwest::Client, url: &str, item: &Value, retries: u32) -> bool {
+    let mut delay = INITIAL_RETRY_DELAY;
+    for attempt in 0..=retries {
+        match client.post(url).json(item).send().await {
+            Ok(response) if response.status().is_success() => return true,
+            Ok(response) => log!(
+                "Webhook POST failed with status {} (attempt {}/{})",
+                response.status(),
+                attempt + 1,
+                retries + 1
+            ),
+            Err(e) => log!(
+                "Webhook POST failed: {} (attempt {}/{})",
+                e,
+                attempt + 1,
+                retries + 1
+            ),
+        }
+        if attempt < retries {
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_a_burst_then_the_rate() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2.0);
+        assert_eq!(bucket.take(start), None);
+        assert_eq!(bucket.take(start), None);
+        assert_eq!(bucket.take(start), Some(Duration::from_millis(500)));
+        assert_eq!(bucket.take(start + Duration::from_millis(500)), None);
+        assert!(bucket.take(start + Duration::from_millis(500)).is_some());
+        // Idle time refills no further than the burst size
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.take(later), None);
+        assert_eq!(bucket.take(later), None);
+        assert!(bucket.take(later).is_some());
+    }
+
+    #[tokio::test]
+    async fn items_beyond_the_queue_are_dropped() {
+        let mut sink = PostSink::start(
+            reqwest::Client::new(),
+            PostConfig {
+                url: "http://127.0.0.1:1/".to_string(),
+                concurrency: 1,
+                retries: 0,
+                min_superchat_micros: None,
+                queue_size: 3,
+                rate: Some(1.0),
+            },
+        );
+        let items: Vec<Value> = (0..10).map(|i| serde_json::json!({"id": i})).collect();
+        // The delivery task hasn't run yet, so only the queue's worth is accepted
+        sink.offer(&serde_json::json!({"items": items}));
+        assert_eq!(sink.dropped, 7);
+        sink.task.abort();
+    }
+}
@@ -0,0 +1,170 @@
+use crate::Args;
+use crate::reconnect;
+use crate::redact;
+use clap::ValueEnum;
+use serde_json::json;
+
+/// Header names whose values are never printed
+const SENSITIVE_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-goog-api-key",
+];
+
+/// Print the configuration a run would use, after applying defaults and environment
+/// variables, as JSON to stderr (`--print-config`)
+pub fn print(args: &Args, resume_chat_id: Option<&str>, resume_page_token: Option<&str>) {
+    let auth = match args.api_key_path.as_deref() {
+        Some("-") => json!({"method": "api_key", "source": "stdin"}),
+        Some(path) => json!({"method": "api_key", "source": "file", "path": path}),
+        None => json!({"method": "none"}),
+    };
+    let oauth = args
+        .access_token_path
+        .as_deref()
+        .map(|path| json!({"access_token_path": path}));
+
+    let config = json!({
+        "version": crate::VERSION,
+        "video_id": args.video_id,
+        "channel_id": args.channel_id,
+        "pick": args.channel_id.as_ref().map(|_| name(&args.pick.unwrap_or(crate::rest::Pick::Only))),
+        "transport": name(&args.transport),
+        "server_url": crate::server_url(),
+        "rest_api_address": crate::rest_api_address(),
+        "auth": auth,
+        "oauth": oauth,
+        "resume": {
+            "enabled": args.resume,
+            "from": args.resume_from.map(|at| humantime::format_rfc3339(at).to_string()),
+            "chat_id": resume_chat_id,
+            "page_token": resume_page_token,
+        },
+        "reconnect": {
+            "policy": name(&crate::reconnect_policy(args)),
+            "wait_secs": args.reconnect_wait_secs,
+            "max_wait_secs": args.reconnect_max_wait_secs,
+            "stream_end_cooldown_secs": args.stream_end_cooldown_secs,
+            "once": args.once,
+            "once_wait_secs": args.once.then_some(args.once_wait_secs),
+            "startup_retries": args.startup_retries,
+            "grpc_status_actions": reconnect::STATUS_ACTIONS
+                .iter()
+                .map(|(name, _, action)| (name.to_string(), json!(action.as_str())))
+                .collect::<serde_json::Map<_, _>>(),
+        },
+        "grpc": {
+            "max_message_size": args.max_message_size,
+            "tls_client_cert": args.tls_client_cert,
+            "tls_client_key": args.tls_client_key,
+        },
+        "http": {
+            "user_agent": args.user_agent,
+            "headers": redact_headers(&args.headers),
+            "proxy": args.proxy.as_deref().map(redact_url),
+            "rest_timeout_secs": args.rest_timeout_secs,
+            "follow_redirects": !args.follow_redirects_off,
+        },
+        "network": {
+            "connect_timeout_secs": args.connect_timeout_secs,
+            "prefer_ipv4": args.prefer_ipv4,
+            "dns_timeout_secs": args.dns_timeout_secs,
+        },
+        "output": {
+            "file": args.output_file,
+            "record_delimiter": name(&args.record_delimiter),
+            "tee": args.tee,
+            "max_bytes": args.max_output_bytes,
+            "format": name(&args.output_format),
+            "buffer_size": args.output_buffer_size,
+            "max_memory_messages": args.max_memory_messages,
+            "overflow": name(&args.overflow),
+            "flush_every": args.flush_every,
+            "flush_interval_millis": args.flush_interval,
+            "write_header": args.write_header,
+            "emit_stream_info": args.emit_stream_info,
+            "emit_control_events": args.emit_control_events,
+            "emit_member_events": args.emit_member_events,
+            "heartbeat_secs": args.heartbeat_secs,
+            "sequence": args.sequence,
+        },
+        "filters": {
+            "only_roles": args.only_roles.iter().map(name).collect::<Vec<_>>(),
+            "filter_expression": args.filter.is_some(),
+            "match_words": args.match_words,
+            "match_regex": args.match_regex.as_ref().map(|regex| regex.as_str()),
+            "exclude_words": args.exclude_words,
+            "top_level_only": args.top_level_only,
+            "threads_only": args.threads_only,
+            "sort_by_published": args.sort_by_published,
+            "mark_first_seen": args.mark_first_seen || args.new_chatters_only,
+            "new_chatters_only": args.new_chatters_only,
+            "anonymize": args.anonymize,
+            "anonymize_salt": args.anonymize_salt.as_ref().map(|_| "<redacted>"),
+            "flatten_author": args.flatten_author,
+        },
+        "webhook": args.post_url.as_deref().map(|url| json!({
+            "url": redact_url(url),
+            "headers": redact_headers(&args.post_headers),
+            "concurrency": args.post_concurrency,
+            "retries": args.post_retries,
+            "min_superchat_micros": args.min_superchat_micros,
+            "relay_rate": args.relay_rate,
+        })),
+        "kafka": args.kafka_brokers.as_deref().map(|brokers| json!({
+            "brokers": brokers,
+            "topic": args.kafka_topic,
+        })),
+        "split_by_type_dir": args.split_by_type_dir,
+        "status_file": args.status_file,
+        "debug_dump_dir": args.debug_dump_dir,
+        "idle_warn_secs": args.idle_warn_secs,
+        "quota_report_secs": args.quota_report_secs,
+        "sample_stats_secs": args.sample_stats_secs,
+        "shutdown_grace_secs": args.shutdown_grace_secs,
+        "max_duration": args
+            .max_duration
+            .map(|limit| humantime::format_duration(limit).to_string()),
+        "start_paused": args.start_paused,
+        "superchat_summary": args.superchat_summary,
+        "strict": args.strict,
+    });
+
+    match serde_json::to_string_pretty(&config) {
+        Ok(json) => log!("{}", json),
+        Err(e) => log!("Failed to format configuration: {}", e),
+    }
+}
+
+/// The command-line spelling of an enum value
+fn name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+/// Hide passwords and `key` parameters in a URL
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("REDACTED"));
+            redact::text(parsed.as_str())
+        }
+        _ => redact::text(url),
+    }
+}
+
+/// Show "Key: Value" headers with the values of credential headers hidden
+fn redact_headers(headers: &[String]) -> Vec<String> {
+    headers
+        .iter()
+        .map(|header| match header.split_once(':') {
+            Some((name, _)) if SENSITIVE_HEADERS.contains(&name.trim().to_lowercase().as_str()) => {
+                format!("{}: REDACTED", name.trim())
+            }
+            _ => header.clone(),
+        })
+        .collect()
+}
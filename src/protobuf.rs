@@ -0,0 +1,121 @@
+//! Length-delimited protobuf archives (`--output-format protobuf`).
+//!
+//! Each response is written as a varint length followed by an encoded
+//! `LiveChatMessageListResponse`, the same framing as `prost::Message::encode_length_delimited`.
+//! Frames are converted from and to the JSON form used everywhere else, so filters, resume
+//! and the offline tools work on either format.
+
+use crate::output::{DelimiterAsNewline, OutputFormat, RecordDelimiter};
+use prost::Message;
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read};
+use yt_grpc_client::LiveChatMessageListResponse;
+
+/// Largest frame accepted when reading, to fail clearly on a corrupt length prefix
+const MAX_FRAME_LEN: u64 = 64 * 1024 * 1024;
+
+/// Encode a response as one length-delimited frame.
+///
+/// Fields that are not part of the protobuf message, such as the normalized `role`, are not
+/// stored.
+pub fn encode(response: &Value) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let response: LiveChatMessageListResponse = serde_json::from_value(response.clone())
+        .map_err(|e| format!("Response can't be encoded as protobuf: {}", e))?;
+    Ok(response.encode_length_delimited_to_vec())
+}
+
+/// Reads the frames of a protobuf archive as JSON responses.
+///
+/// A truncated or corrupt frame (e.g. from a crash mid-write) ends the iteration with a
+/// warning, since later frame boundaries can't be found.
+pub struct FrameReader<R> {
+    inner: R,
+    /// Number of frames read so far, for error messages
+    frames: u64,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(inner: R) -> Self {
+        FrameReader { inner, frames: 0 }
+    }
+
+    fn read_frame(&mut self) -> Result<Option<Value>, Box<dyn std::error::Error>> {
+        let Some(len) = read_varint(&mut self.inner)? else {
+            return Ok(None);
+        };
+        if len > MAX_FRAME_LEN {
+            return Err(format!("frame length {} is implausibly large", len).into());
+        }
+        let mut buf = vec![0; len as usize];
+        self.inner.read_exact(&mut buf)?;
+        let response = LiveChatMessageListResponse::decode(buf.as_slice())?;
+        Ok(Some(serde_json::to_value(&response)?))
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self.read_frame() {
+            Ok(frame) => {
+                self.frames += u64::from(frame.is_some());
+                frame
+            }
+            Err(e) => {
+                log!("Stopping at unreadable frame {}: {}", self.frames + 1, e);
+                None
+            }
+        }
+    }
+}
+
+/// Read a varint length prefix. Returns None at a clean end of input.
+fn read_varint(reader: &mut impl Read) -> std::io::Result<Option<u64>> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            if shift == 0 {
+                return Ok(None);
+            }
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "varint is too long",
+    ))
+}
+
+/// Open a protobuf archive. Returns None if the file doesn't exist.
+pub fn open(path: &str) -> Result<Option<FrameReader<BufReader<std::fs::File>>>, std::io::Error> {
+    match std::fs::File::open(path) {
+        Ok(file) => Ok(Some(FrameReader::new(BufReader::new(file)))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// The records of an archive in either format, as JSON lines (`delimiter` separates JSON
+/// records; frames need none)
+pub fn json_lines(
+    input: &str,
+    format: OutputFormat,
+    delimiter: RecordDelimiter,
+) -> Result<Box<dyn Iterator<Item = std::io::Result<String>>>, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(input)
+        .map_err(|e| format!("Failed to open input file '{}': {}", input, e))?;
+    Ok(match format {
+        OutputFormat::Json => {
+            Box::new(BufReader::new(DelimiterAsNewline::new(file, delimiter)).lines())
+        }
+        OutputFormat::Protobuf => {
+            Box::new(FrameReader::new(BufReader::new(file)).map(|frame| Ok(frame.to_string())))
+        }
+    })
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Documented quota cost of a videos.list call
+const VIDEOS_LIST_COST: u64 = 1;
+
+/// Documented quota cost of a liveChatMessages.list call
+const LIVE_CHAT_MESSAGES_LIST_COST: u64 = 5;
+
+/// Documented quota cost of a search.list call
+const SEARCH_LIST_COST: u64 = 100;
+
+/// Quota cost assumed for opening a streamList stream (charged like liveChatMessages.list)
+const STREAM_LIST_COST: u64 = 5;
+
+/// YouTube Data API calls the fetcher makes
+#[derive(Clone, Copy, Debug)]
+pub enum Endpoint {
+    /// videos.list
+    Videos,
+    /// liveChatMessages.list
+    LiveChatMessages,
+    /// streamList (counted once per stream opened)
+    LiveChatStream,
+    /// search.list
+    Search,
+}
+
+/// Counts API calls made during the run to estimate the quota units consumed.
+///
+/// Clones share the same counters, so the stream and the main loop can both hold one.
+#[derive(Clone, Debug, Default)]
+pub struct ApiCalls {
+    counts: Arc<[AtomicU64; 4]>,
+}
+
+impl ApiCalls {
+    /// Count one request to the endpoint (failed requests are still charged)
+    pub fn record(&self, endpoint: Endpoint) {
+        self.counts[endpoint as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, endpoint: Endpoint) -> u64 {
+        self.counts[endpoint as usize].load(Ordering::Relaxed)
+    }
+
+    /// Number of calls made to all endpoints
+    pub fn total(&self) -> u64 {
+        self.counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Estimated quota units consumed so far
+    pub fn quota_units(&self) -> u64 {
+        self.count(Endpoint::Videos) * VIDEOS_LIST_COST
+            + self.count(Endpoint::LiveChatMessages) * LIVE_CHAT_MESSAGES_LIST_COST
+            + self.count(Endpoint::LiveChatStream) * STREAM_LIST_COST
+            + self.count(Endpoint::Search) * SEARCH_LIST_COST
+    }
+
+    /// One-line description of the calls made and the estimated quota used
+    pub fn summary(&self) -> String {
+        format!(
+            "{} search.list, {} videos.list, {} liveChatMessages.list, {} streamList calls; ~{} quota units",
+            self.count(Endpoint::Search),
+            self.count(Endpoint::Videos),
+            self.count(Endpoint::LiveChatMessages),
+            self.count(Endpoint::LiveChatStream),
+            self.quota_units()
+        )
+    }
+}
@@ -0,0 +1,298 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// Wait before the first startup retry; doubles after each failure
+const STARTUP_RETRY_BASE: Duration = Duration::from_secs(1);
+
+/// Longest wait between startup retries
+const STARTUP_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// Decides how long to wait before each reconnect attempt
+pub trait ReconnectPolicy: Send {
+    /// Delay before reconnect attempt `attempt` (1 for the first attempt after the connection
+    /// was lost), or None to give up
+    fn next_delay(&mut self, attempt: u32, last_error: &dyn std::error::Error) -> Option<Duration>;
+}
+
+/// Built-in policies, selected with `--reconnect-policy`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyKind {
+    /// Always wait --reconnect-wait-secs
+    Fixed,
+    /// Double the wait after each failed attempt, starting at --reconnect-wait-secs
+    Exponential,
+    /// Like exponential, but never wait longer than --reconnect-max-wait-secs
+    ExponentialCapped,
+    /// Exit instead of reconnecting
+    None,
+}
+
+impl PolicyKind {
+    pub fn build(self, wait: Duration, max_wait: Duration) -> Box<dyn ReconnectPolicy> {
+        match self {
+            PolicyKind::Fixed => Box::new(Fixed(wait)),
+            PolicyKind::Exponential => Box::new(Exponential {
+                base: wait,
+                max: None,
+            }),
+            PolicyKind::ExponentialCapped => Box::new(Exponential {
+                base: wait,
+                max: Some(max_wait),
+            }),
+            PolicyKind::None => Box::new(GiveUp),
+        }
+    }
+}
+
+/// The same wait before every attempt
+pub struct Fixed(pub Duration);
+
+impl ReconnectPolicy for Fixed {
+    fn next_delay(
+        &mut self,
+        _attempt: u32,
+        _last_error: &dyn std::error::Error,
+    ) -> Option<Duration> {
+        Some(self.0)
+    }
+}
+
+/// A wait that doubles with each attempt, optionally capped
+pub struct Exponential {
+    pub base: Duration,
+    pub max: Option<Duration>,
+}
+
+impl ReconnectPolicy for Exponential {
+    fn next_delay(
+        &mut self,
+        attempt: u32,
+        _last_error: &dyn std::error::Error,
+    ) -> Option<Duration> {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self.base.saturating_mul(factor);
+        Some(self.max.map_or(delay, |max| delay.min(max)))
+    }
+}
+
+/// Never reconnect
+pub struct GiveUp;
+
+impl ReconnectPolicy for GiveUp {
+    fn next_delay(
+        &mut self,
+        _attempt: u32,
+        _last_error: &dyn std::error::Error,
+    ) -> Option<Duration> {
+        None
+    }
+}
+
+/// How the wait before a reconnect is adjusted after a gRPC error with a given status
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusAction {
+    /// Wait at least the long wait (--reconnect-max-wait-secs), e.g. while quota is exhausted
+    LongBackoff,
+    /// Wait the short wait (--reconnect-wait-secs) without growing it, for transient outages
+    ShortBackoff,
+    /// Retry at once, with the API key and access token re-read as before every attempt, so a
+    /// rotated key or refreshed token is picked up; if that is rejected too, give up
+    RefreshCredentials,
+}
+
+impl StatusAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StatusAction::LongBackoff => "long_backoff",
+            StatusAction::ShortBackoff => "short_backoff",
+            StatusAction::RefreshCredentials => "refresh_credentials",
+        }
+    }
+}
+
+/// gRPC status codes handled differently from the reconnect policy; other codes (and REST
+/// errors) follow the policy
+pub const STATUS_ACTIONS: &[(&str, tonic::Code, StatusAction)] = &[
+    (
+        "RESOURCE_EXHAUSTED",
+        tonic::Code::ResourceExhausted,
+        StatusAction::LongBackoff,
+    ),
+    (
+        "UNAVAILABLE",
+        tonic::Code::Unavailable,
+        StatusAction::ShortBackoff,
+    ),
+    (
+        "UNAUTHENTICATED",
+        tonic::Code::Unauthenticated,
+        StatusAction::RefreshCredentials,
+    ),
+];
+
+/// The action for an error, if it is a gRPC status listed in [`STATUS_ACTIONS`]
+fn status_action(
+    error: &(dyn std::error::Error + 'static),
+) -> Option<(&'static str, StatusAction)> {
+    let status = error.downcast_ref::<tonic::Status>()?;
+    STATUS_ACTIONS
+        .iter()
+        .find(|(_, code, _)| *code == status.code())
+        .map(|(name, _, action)| (*name, *action))
+}
+
+/// Numbers the attempts since the connection was last healthy and asks the policy for each
+/// delay
+pub struct Reconnector {
+    policy: Box<dyn ReconnectPolicy>,
+    attempt: u32,
+    /// Short and long waits used by [`STATUS_ACTIONS`] (the policy alone decides when None)
+    status_waits: Option<(Duration, Duration)>,
+    /// The action taken for the previous attempt, if any
+    last_action: Option<StatusAction>,
+    /// Set when giving up because re-read credentials were rejected again
+    credentials_rejected: bool,
+}
+
+impl Reconnector {
+    pub fn new(policy: Box<dyn ReconnectPolicy>) -> Self {
+        Reconnector {
+            policy,
+            attempt: 0,
+            status_waits: None,
+            last_action: None,
+            credentials_rejected: false,
+        }
+    }
+
+    /// Adjust the policy's delays for the gRPC statuses in [`STATUS_ACTIONS`]
+    pub fn status_waits(mut self, short: Duration, long: Duration) -> Self {
+        self.status_waits = Some((short, long));
+        self
+    }
+
+    /// Delay before the next attempt, or None to give up. A policy that gives up always wins.
+    pub fn next_delay(
+        &mut self,
+        last_error: &(dyn std::error::Error + 'static),
+    ) -> Option<Duration> {
+        self.attempt = self.attempt.saturating_add(1);
+        let delay = self.policy.next_delay(self.attempt, last_error)?;
+        let Some((short, long)) = self.status_waits else {
+            return Some(delay);
+        };
+        let last_action = self.last_action.take();
+        let Some((name, action)) = status_action(last_error) else {
+            return Some(delay);
+        };
+        self.last_action = Some(action);
+        let delay = match action {
+            StatusAction::LongBackoff => delay.max(long),
+            StatusAction::ShortBackoff => short,
+            StatusAction::RefreshCredentials if last_action != Some(action) => Duration::ZERO,
+            StatusAction::RefreshCredentials => {
+                log!(
+                    "gRPC status {}: rejected again after re-reading credentials",
+                    name
+                );
+                self.credentials_rejected = true;
+                return None;
+            }
+        };
+        log!(
+            "gRPC status {}: {} ({} seconds)",
+            name,
+            action.as_str().replace('_', " "),
+            delay.as_secs()
+        );
+        Some(delay)
+    }
+
+    /// Whether the last [`next_delay`](Self::next_delay) gave up because the credentials were
+    /// still rejected after being re-read
+    pub fn credentials_rejected(&self) -> bool {
+        self.credentials_rejected
+    }
+
+    /// Start counting attempts from 1 again (called once responses are arriving)
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+        self.last_action = None;
+    }
+}
+
+/// The error passed to the policy when the server closed the stream cleanly
+#[derive(Debug)]
+pub struct StreamEnded;
+
+impl std::fmt::Display for StreamEnded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "stream ended")
+    }
+}
+
+impl std::error::Error for StreamEnded {}
+
+/// How [`retry_with_backoff`] retries a failed call
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 disables retrying)
+    pub retries: u32,
+    /// Wait before the first retry; doubles after each further failure
+    pub base: Duration,
+    /// Longest wait between retries
+    pub max: Duration,
+}
+
+impl RetryPolicy {
+    /// The backoff used for startup steps, with the given number of retries
+    pub fn startup(retries: u32) -> Self {
+        RetryPolicy {
+            retries,
+            base: STARTUP_RETRY_BASE,
+            max: STARTUP_RETRY_MAX,
+        }
+    }
+}
+
+/// Run a one-off call, retrying it with exponential backoff as `policy` allows before
+/// returning the last error. Errors for which `is_retryable` returns false are returned at
+/// once.
+pub async fn retry_with_backoff<T, F, Fut>(
+    what: &str,
+    policy: RetryPolicy,
+    is_retryable: fn(&(dyn std::error::Error + 'static)) -> bool,
+    mut step: F,
+) -> Result<T, Box<dyn std::error::Error>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Box<dyn std::error::Error>>>,
+{
+    let mut backoff = Exponential {
+        base: policy.base,
+        max: Some(policy.max),
+    };
+    let retries = policy.retries;
+    let mut attempt = 0;
+    loop {
+        match step().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < retries && is_retryable(e.as_ref()) => {
+                attempt += 1;
+                let delay = backoff
+                    .next_delay(attempt, e.as_ref())
+                    .unwrap_or(policy.max);
+                log!(
+                    "Failed to {}: {}; retrying in {} seconds ({}/{})...",
+                    what,
+                    e,
+                    delay.as_secs(),
+                    attempt,
+                    retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
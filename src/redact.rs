@@ -0,0 +1,98 @@
+//! Scrubbing secrets from anything written to stderr or to debug dumps.
+//!
+//! Error messages can carry request URLs (with `key=...`), response bodies and headers, so
+//! every log line goes through [`text`] via the `log!` macro. Besides the
+//! patterns that look like credentials, the API key and access token read at startup are
+//! registered with [`add_secret`] and removed wherever they appear.
+
+use regex::Regex;
+use std::sync::{LazyLock, RwLock};
+
+/// Secrets shorter than this aren't registered, since replacing them would mangle ordinary text
+const MIN_SECRET_LEN: usize = 8;
+
+/// What a secret is replaced with
+const REDACTED: &str = "REDACTED";
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// `key=` query parameters, as sent by the REST client
+static KEY_PARAM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"([?&]key=)[^&\s\x22'<>]+").expect("valid regex"));
+
+/// `Bearer <token>` credentials, as sent with `--access-token-path` or custom headers
+static BEARER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9._~+/=-]+").expect("valid regex"));
+
+/// Log a line to stderr with secrets redacted; takes the same arguments as `eprintln!`
+macro_rules! log {
+    ($($arg:tt)*) => {
+        eprintln!("{}", $crate::redact::text(&format!($($arg)*)))
+    };
+}
+
+/// Redact this secret from all further output (e.g. the API key once it's been read).
+/// Reloaded credentials are added alongside the old ones.
+pub fn add_secret(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// The text with registered secrets, `key=` parameters and bearer tokens replaced
+pub fn text(text: &str) -> String {
+    let mut text = KEY_PARAM
+        .replace_all(text, format!("${{1}}{}", REDACTED))
+        .into_owned();
+    text = BEARER
+        .replace_all(&text, format!("${{1}}{}", REDACTED))
+        .into_owned();
+    for secret in SECRETS.read().unwrap_or_else(|e| e.into_inner()).iter() {
+        if text.contains(secret.as_str()) {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_containing_the_api_key_is_redacted() {
+        let key = "AIzaSyTestKeyForRedaction0123456789";
+        add_secret(key);
+        let error: Box<dyn std::error::Error> =
+            format!("API error: invalid API key '{}' in request", key).into();
+        let shown = text(&error.to_string());
+        assert!(!shown.contains(key), "key leaked: {}", shown);
+        assert_eq!(shown, "API error: invalid API key 'REDACTED' in request");
+    }
+
+    #[test]
+    fn key_parameter_is_redacted_without_registration() {
+        let shown = text("GET https://example.com/videos?part=snippet&key=unregistered-key&id=x");
+        assert_eq!(
+            shown,
+            "GET https://example.com/videos?part=snippet&key=REDACTED&id=x"
+        );
+    }
+
+    #[test]
+    fn bearer_token_is_redacted() {
+        let shown = text("authorization: Bearer ya29.a0-token_value");
+        assert_eq!(shown, "authorization: Bearer REDACTED");
+    }
+
+    #[test]
+    fn short_secrets_are_ignored() {
+        add_secret("abc");
+        assert_eq!(text("abc abc"), "abc abc");
+    }
+}
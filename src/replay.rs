@@ -0,0 +1,94 @@
+use crate::message::{self, Item};
+use crate::output::{
+    FlushPolicy, Output, OutputConfig, OutputFormat, OverflowPolicy, RecordDelimiter,
+};
+use std::time::{Duration, SystemTime};
+
+/// Grace period for flushing stdout once the archive has been replayed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Re-emit an NDJSON archive to stdout, spacing responses by the gap between their
+/// `published_at` timestamps divided by `speed`.
+///
+/// Records are written unchanged through the same output path as live streaming. Header and
+/// control records are passed through without delay, as are responses without a parseable
+/// timestamp.
+pub async fn replay(
+    input: &str,
+    speed: f64,
+    format: OutputFormat,
+    delimiter: RecordDelimiter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !(speed.is_finite() && speed > 0.0) {
+        return Err("--speed must be a positive number".into());
+    }
+
+    let lines = crate::protobuf::json_lines(input, format, delimiter)?;
+    log!("Replaying {} at {}x speed", input, speed);
+
+    let mut output = Output::open(OutputConfig {
+        path: None,
+        tee: false,
+        max_bytes: None,
+        emit_control_events: true,
+        emit_member_events: false,
+        buffer_size: 1,
+        overflow: OverflowPolicy::Block,
+        flush: FlushPolicy::default(),
+        delimiter,
+        format: OutputFormat::Json,
+    })?;
+
+    let mut records = 0u64;
+    let mut malformed = 0u64;
+    let mut previous: Option<SystemTime> = None;
+    for (index, line) in lines.enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: serde_json::Value = match serde_json::from_str(&line) {
+            Ok(value) => value,
+            Err(e) => {
+                log!("Skipping malformed line {}: {}", index + 1, e);
+                malformed += 1;
+                continue;
+            }
+        };
+
+        if let Some(published_at) = first_published_at(&record) {
+            if let Some(previous) = previous {
+                let gap = published_at.duration_since(previous).unwrap_or_default();
+                tokio::select! {
+                    _ = tokio::time::sleep(gap.div_f64(speed)) => {}
+                    _ = tokio::signal::ctrl_c() => {
+                        log!("Received Ctrl+C, stopping replay");
+                        break;
+                    }
+                }
+            }
+            previous = Some(published_at);
+        }
+
+        output.write_record(line).await?;
+        records += 1;
+    }
+
+    output.shutdown(SHUTDOWN_GRACE).await;
+    log!(
+        "Replayed {} records ({} malformed lines skipped)",
+        records,
+        malformed
+    );
+    Ok(())
+}
+
+/// Timestamp of the first chat item in a response, if any
+fn first_published_at(record: &serde_json::Value) -> Option<SystemTime> {
+    if record.get("event").is_some() {
+        return None;
+    }
+    let item = message::items(record).first()?;
+    humantime::parse_rfc3339_weak(Item(item).published_at()?).ok()
+}
@@ -0,0 +1,754 @@
+use crate::debug_dump::DebugDump;
+use crate::errors::CodedError;
+use crate::quota::{ApiCalls, Endpoint};
+use std::collections::HashMap;
+use std::time::Duration;
+use yt_grpc_client::NetworkOptions;
+
+/// Polling interval used when the server does not advertise one
+const DEFAULT_POLLING_INTERVAL_MILLIS: u64 = 5000;
+
+/// Build the HTTP client shared by all REST calls, with the given user agent and extra
+/// headers (each formatted as "Key: Value"). Every request fails once `timeout` has elapsed.
+/// `network` sets the connect timeout and, if needed, a resolver that bounds DNS lookups
+/// and prefers IPv4.
+///
+/// Without an explicit proxy, the standard HTTP_PROXY/HTTPS_PROXY/ALL_PROXY/NO_PROXY
+/// environment variables are honored.
+pub fn build_client(
+    user_agent: &str,
+    headers: &[String],
+    proxy: Option<&str>,
+    timeout: Duration,
+    follow_redirects: bool,
+    network: NetworkOptions,
+) -> Result<reqwest::Client, Box<dyn std::error::Error>> {
+    let mut header_map = reqwest::header::HeaderMap::new();
+    for header in headers {
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid header '{}': expected \"Key: Value\"", header))?;
+        let name = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|e| format!("Invalid header name in '{}': {}", header, e))?;
+        let value = reqwest::header::HeaderValue::from_str(value.trim())
+            .map_err(|e| format!("Invalid header value in '{}': {}", header, e))?;
+        header_map.append(name, value);
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .default_headers(header_map)
+        .timeout(timeout);
+
+    if let Some(limit) = network.connect_timeout {
+        builder = builder.connect_timeout(limit);
+    }
+    if network.custom_resolver() {
+        builder = builder.dns_resolver(std::sync::Arc::new(Resolver(network)));
+    }
+
+    if !follow_redirects {
+        builder = builder.redirect(reqwest::redirect::Policy::none());
+    }
+
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| format!("Invalid proxy URL '{}': {}", proxy, e))?;
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Resolves host names with [`yt_grpc_client::resolve`], so REST and gRPC connections
+/// honor the same `--prefer-ipv4` and `--dns-timeout-secs`
+struct Resolver(NetworkOptions);
+
+impl reqwest::dns::Resolve for Resolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let options = self.0;
+        Box::pin(async move {
+            // The port is filled in by the connector
+            let addrs = yt_grpc_client::resolve(name.as_str(), 0, &options).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Turn a timed-out request into a clear error; other failures are returned unchanged
+fn send_error(error: reqwest::Error) -> Box<dyn std::error::Error> {
+    if error.is_timeout() {
+        let url = error.url().map(|url| crate::redact::text(url.as_str()));
+        return CodedError::new(
+            "timeout",
+            format!(
+                "Request to {} timed out (see --rest-timeout-secs)",
+                url.as_deref().unwrap_or("the REST API")
+            ),
+        )
+        .into();
+    }
+    error.into()
+}
+
+/// What videos.list reports about a live stream
+#[derive(Clone, Debug)]
+pub struct StreamInfo {
+    /// The active live chat ID
+    pub chat_id: String,
+    pub title: Option<String>,
+    pub channel_title: Option<String>,
+    /// Current viewer count (absent once the stream has ended or when hidden by the owner)
+    pub concurrent_viewers: Option<u64>,
+    /// Like count (absent when hidden by the owner)
+    pub likes: Option<u64>,
+}
+
+/// Fetch the active live chat ID and basic stream metadata for a video from the videos.list
+/// endpoint, authenticating with the API key and/or an OAuth access token
+pub async fn fetch_stream_info(
+    client: &reqwest::Client,
+    rest_api_address: &str,
+    video_id: &str,
+    api_key: Option<&str>,
+    access_token: Option<&str>,
+    debug_dump: Option<&DebugDump>,
+    api_calls: &ApiCalls,
+) -> Result<StreamInfo, Box<dyn std::error::Error>> {
+    let mut url = format!(
+        "{}/youtube/v3/videos?part=liveStreamingDetails,snippet,statistics&id={}",
+        rest_api_address, video_id
+    );
+
+    // Add API key as query parameter if provided
+    if let Some(key) = api_key {
+        url.push_str(&format!("&key={}", key));
+    }
+
+    // An OAuth token also sees the caller's own private and unlisted broadcasts
+    let mut request = client.get(&url);
+    if let Some(token) = access_token {
+        request = request.bearer_auth(token);
+    }
+
+    api_calls.record(Endpoint::Videos);
+    let response = request.send().await.map_err(send_error)?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+        if let Some(dump) = debug_dump {
+            dump.http_response("videos-list", &url, status, &headers, &body);
+        }
+        return Err(Box::new(HttpError {
+            request: "fetch video data",
+            status,
+            body,
+        }));
+    }
+
+    let body: serde_json::Value = response.json().await?;
+
+    // Extract the activeLiveChatId from the response
+    let items = body.get("items").ok_or("Response missing 'items' field")?;
+
+    let items_array = items.as_array().ok_or("'items' field is not an array")?;
+
+    let first_item = items_array.first().ok_or_else(|| VideoNotFound {
+        video_id: video_id.to_string(),
+    })?;
+
+    let live_streaming_details = first_item
+        .get("liveStreamingDetails")
+        .ok_or("Video does not have live streaming details (not a live video)")?;
+
+    let chat_id = live_streaming_details
+        .get("activeLiveChatId")
+        .and_then(|id| id.as_str())
+        .ok_or("No active live chat ID found (stream may not be active)")?;
+
+    let snippet_str = |name| {
+        first_item
+            .get("snippet")
+            .and_then(|snippet| snippet.get(name))
+            .and_then(|value| value.as_str())
+            .map(|s| s.to_string())
+    };
+
+    // REST encodes uint64 counts as strings
+    let count = |value: &serde_json::Value| match value {
+        serde_json::Value::String(s) => s.parse().ok(),
+        other => other.as_u64(),
+    };
+    let concurrent_viewers = live_streaming_details
+        .get("concurrentViewers")
+        .and_then(count);
+    let likes = first_item
+        .get("statistics")
+        .and_then(|statistics| statistics.get("likeCount"))
+        .and_then(count);
+
+    Ok(StreamInfo {
+        chat_id: chat_id.to_string(),
+        title: snippet_str("title"),
+        channel_title: snippet_str("channelTitle"),
+        concurrent_viewers,
+        likes,
+    })
+}
+
+/// videos.list answered successfully but returned no video for the ID (mistyped, private or
+/// deleted)
+#[derive(Debug)]
+pub struct VideoNotFound {
+    pub video_id: String,
+}
+
+impl std::fmt::Display for VideoNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No video found with ID '{}'", self.video_id)
+    }
+}
+
+impl std::error::Error for VideoNotFound {}
+
+/// How to choose among several live videos found for a channel
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pick {
+    /// Require exactly one live video and list the candidates otherwise
+    Only,
+    /// Use the first live video search returns
+    First,
+}
+
+/// A live video found by search.list
+#[derive(Clone, Debug)]
+pub struct LiveVideo {
+    pub video_id: String,
+    pub title: Option<String>,
+}
+
+/// Find the videos a channel is currently live streaming with search.list
+pub async fn search_live_videos(
+    client: &reqwest::Client,
+    rest_api_address: &str,
+    channel_id: &str,
+    api_key: Option<&str>,
+    access_token: Option<&str>,
+    debug_dump: Option<&DebugDump>,
+    api_calls: &ApiCalls,
+) -> Result<Vec<LiveVideo>, Box<dyn std::error::Error>> {
+    search_videos(
+        client,
+        rest_api_address,
+        channel_id,
+        "live",
+        api_key,
+        access_token,
+        debug_dump,
+        api_calls,
+    )
+    .await
+}
+
+/// Find a channel's videos with the given search.list `eventType` (`live`, `upcoming` or
+/// `completed`)
+#[allow(clippy::too_many_arguments)]
+pub async fn search_videos(
+    client: &reqwest::Client,
+    rest_api_address: &str,
+    channel_id: &str,
+    event_type: &str,
+    api_key: Option<&str>,
+    access_token: Option<&str>,
+    debug_dump: Option<&DebugDump>,
+    api_calls: &ApiCalls,
+) -> Result<Vec<LiveVideo>, Box<dyn std::error::Error>> {
+    let mut url = format!(
+        "{}/youtube/v3/search?part=snippet&eventType={}&type=video&channelId={}",
+        rest_api_address, event_type, channel_id
+    );
+
+    if let Some(key) = api_key {
+        url.push_str(&format!("&key={}", key));
+    }
+
+    let mut request = client.get(&url);
+    if let Some(token) = access_token {
+        request = request.bearer_auth(token);
+    }
+
+    api_calls.record(Endpoint::Search);
+    let response = request.send().await.map_err(send_error)?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+        if let Some(dump) = debug_dump {
+            dump.http_response("search-list", &url, status, &headers, &body);
+        }
+        return Err(Box::new(HttpError {
+            request: "search for videos",
+            status,
+            body,
+        }));
+    }
+
+    let body: serde_json::Value = response.json().await?;
+    let items = body
+        .get("items")
+        .and_then(|items| items.as_array())
+        .ok_or("Search response missing 'items' array")?;
+
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let video_id = item.get("id")?.get("videoId")?.as_str()?;
+            let title = item
+                .get("snippet")
+                .and_then(|snippet| snippet.get("title"))
+                .and_then(|title| title.as_str());
+            Some(LiveVideo {
+                video_id: video_id.to_string(),
+                title: title.map(|t| t.to_string()),
+            })
+        })
+        .collect())
+}
+
+/// Look up when each video started (`actualStartTime`) or, if it hasn't yet, when it is
+/// scheduled to (`scheduledStartTime`), with one videos.list call. Videos without either
+/// are left out.
+pub async fn fetch_start_times(
+    client: &reqwest::Client,
+    rest_api_address: &str,
+    video_ids: &[&str],
+    api_key: Option<&str>,
+    access_token: Option<&str>,
+    debug_dump: Option<&DebugDump>,
+    api_calls: &ApiCalls,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    if video_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut url = format!(
+        "{}/youtube/v3/videos?part=liveStreamingDetails&id={}",
+        rest_api_address,
+        video_ids.join(",")
+    );
+    if let Some(key) = api_key {
+        url.push_str(&format!("&key={}", key));
+    }
+
+    let mut request = client.get(&url);
+    if let Some(token) = access_token {
+        request = request.bearer_auth(token);
+    }
+
+    api_calls.record(Endpoint::Videos);
+    let response = request.send().await.map_err(send_error)?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await?;
+        if let Some(dump) = debug_dump {
+            dump.http_response("videos-list", &url, status, &headers, &body);
+        }
+        return Err(Box::new(HttpError {
+            request: "fetch video data",
+            status,
+            body,
+        }));
+    }
+
+    let body: serde_json::Value = response.json().await?;
+    let items = body
+        .get("items")
+        .and_then(|items| items.as_array())
+        .ok_or("Response missing 'items' field")?;
+
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let video_id = item.get("id")?.as_str()?;
+            let details = item.get("liveStreamingDetails")?;
+            let start = details
+                .get("actualStartTime")
+                .or_else(|| details.get("scheduledStartTime"))?
+                .as_str()?;
+            Some((video_id.to_string(), start.to_string()))
+        })
+        .collect())
+}
+
+/// A non-success response from liveChatMessages.list
+#[derive(Debug)]
+pub struct ChatMessagesError {
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for ChatMessagesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to fetch chat messages (status {}): {}",
+            self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for ChatMessagesError {}
+
+/// A videos.list or search.list request answered with an error status
+#[derive(Debug)]
+pub struct HttpError {
+    /// What the request was for, e.g. "fetch video data"
+    pub request: &'static str,
+    pub status: reqwest::StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to {} (status {}): {}",
+            self.request, self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// Whether a failed REST call is worth retrying. Error statuses are retried only for 5xx and
+/// 429 (Too Many Requests); other 4xx responses, and a video that doesn't exist, won't change
+/// on a retry. Anything else, such as a network error or timeout, is treated as transient.
+pub fn is_retryable(error: &(dyn std::error::Error + 'static)) -> bool {
+    if error.is::<VideoNotFound>() {
+        return false;
+    }
+    let status = match (
+        error.downcast_ref::<HttpError>(),
+        error.downcast_ref::<ChatMessagesError>(),
+    ) {
+        (Some(error), _) => error.status,
+        (_, Some(error)) => error.status,
+        (None, None) => return true,
+    };
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Polls the REST liveChatMessages.list endpoint, honoring the server's polling interval
+pub struct LiveChatPoller {
+    client: reqwest::Client,
+    rest_api_address: String,
+    api_key: Option<String>,
+    access_token: Option<String>,
+    chat_id: String,
+    page_token: Option<String>,
+    /// Response fetched eagerly by `prime`, returned by the next call to `next`
+    pending: Option<serde_json::Value>,
+    /// Delay before the next request (None for the first request)
+    wait: Option<Duration>,
+    /// Set once the server reports the chat as offline
+    ended: bool,
+    debug_dump: Option<DebugDump>,
+    api_calls: ApiCalls,
+}
+
+impl LiveChatPoller {
+    pub fn new(
+        client: reqwest::Client,
+        rest_api_address: String,
+        api_key: Option<String>,
+        chat_id: String,
+        page_token: Option<String>,
+        debug_dump: Option<DebugDump>,
+        api_calls: ApiCalls,
+    ) -> Self {
+        LiveChatPoller {
+            client,
+            rest_api_address,
+            api_key,
+            access_token: None,
+            chat_id,
+            page_token,
+            pending: None,
+            wait: None,
+            ended: false,
+            debug_dump,
+            api_calls,
+        }
+    }
+
+    /// OAuth access token sent as a bearer token with each request
+    pub fn access_token(mut self, access_token: Option<String>) -> Self {
+        self.access_token = access_token;
+        self
+    }
+
+    /// Fetch the first page immediately so request errors surface when the stream is opened
+    pub async fn prime(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.pending = Some(self.poll().await?);
+        Ok(())
+    }
+
+    /// Wait for the polling interval, then fetch the next page of messages.
+    /// Returns None once the chat has gone offline.
+    pub async fn next(&mut self) -> Option<Result<serde_json::Value, Box<dyn std::error::Error>>> {
+        if let Some(body) = self.pending.take() {
+            return Some(Ok(body));
+        }
+
+        if self.ended {
+            return None;
+        }
+
+        if let Some(wait) = self.wait {
+            tokio::time::sleep(wait).await;
+        }
+
+        Some(self.poll().await)
+    }
+
+    async fn poll(&mut self) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let mut params = vec![
+            ("part", "snippet,authorDetails"),
+            ("liveChatId", self.chat_id.as_str()),
+        ];
+        if let Some(token) = &self.page_token {
+            params.push(("pageToken", token));
+        }
+        if let Some(key) = &self.api_key {
+            params.push(("key", key));
+        }
+        // Percent-encoded, since page tokens are opaque and may contain reserved characters
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/youtube/v3/liveChat/messages", self.rest_api_address),
+            &params,
+        )?;
+
+        let mut request = self.client.get(url.clone());
+        if let Some(token) = &self.access_token {
+            request = request.bearer_auth(token);
+        }
+
+        self.api_calls.record(Endpoint::LiveChatMessages);
+        let response = request.send().await.map_err(send_error)?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.text().await?;
+            if let Some(ref dump) = self.debug_dump {
+                dump.http_response("live-chat-messages", url.as_str(), status, &headers, &body);
+            }
+            return Err(Box::new(ChatMessagesError { status, body }));
+        }
+
+        // Convert to the same shape as the gRPC output so consumers and --resume see one
+        // shape regardless of transport
+        let body = to_grpc_shape(response.json().await?);
+
+        self.page_token = body
+            .get("next_page_token")
+            .and_then(|token| token.as_str())
+            .map(|s| s.to_string());
+
+        let interval = body
+            .get("polling_interval_millis")
+            .and_then(|ms| ms.as_u64())
+            .unwrap_or(DEFAULT_POLLING_INTERVAL_MILLIS);
+        self.wait = Some(Duration::from_millis(interval));
+
+        if body.get("offline_at").is_some() {
+            self.ended = true;
+        }
+
+        Ok(body)
+    }
+}
+
+/// Fields that are uint64 in the gRPC API, which REST encodes as strings
+const UINT64_FIELDS: [&str; 2] = ["amount_micros", "ban_duration_seconds"];
+
+/// Convert a REST liveChatMessages response to the JSON the gRPC transport writes: snake_case
+/// keys, enums as their numbers and uint64 fields as numbers
+fn to_grpc_shape(value: serde_json::Value) -> serde_json::Value {
+    let mut value = snake_case_keys(value);
+    uint64_numbers(&mut value);
+    if let Some(serde_json::Value::Array(items)) = value.get_mut("items") {
+        items.iter_mut().for_each(enum_numbers);
+    }
+    if let Some(poll) = value.get_mut("active_poll_item") {
+        enum_numbers(poll);
+    }
+    value
+}
+
+/// Replace the REST enum names in a chat message with their gRPC numbers
+fn enum_numbers(item: &mut serde_json::Value) {
+    let Some(snippet) = item.get_mut("snippet") else {
+        return;
+    };
+    replace_enum(snippet, "type", crate::message::type_code);
+    if let Some(details) = snippet.get_mut("user_banned_details") {
+        replace_enum(details, "ban_type", |name| match name {
+            "permanent" => Some(1),
+            "temporary" => Some(2),
+            _ => None,
+        });
+    }
+}
+
+/// Replace a REST enum name in `object[key]` with its gRPC number, leaving unknown names as is
+fn replace_enum(object: &mut serde_json::Value, key: &str, code: impl Fn(&str) -> Option<i64>) {
+    if let Some(value) = object.get_mut(key)
+        && let Some(number) = value.as_str().and_then(&code)
+    {
+        *value = number.into();
+    }
+}
+
+/// Recursively turn string values of [`UINT64_FIELDS`] into numbers
+fn uint64_numbers(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if UINT64_FIELDS.contains(&key.as_str())
+                    && let Some(number) = value.as_str().and_then(|s| s.parse::<u64>().ok())
+                {
+                    *value = number.into();
+                } else {
+                    uint64_numbers(value);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(uint64_numbers),
+        _ => {}
+    }
+}
+
+/// Recursively rename camelCase object keys (REST) to snake_case (gRPC JSON)
+fn snake_case_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| (to_snake_case(&key), snake_case_keys(value)))
+            .collect(),
+        serde_json::Value::Array(items) => items.into_iter().map(snake_case_keys).collect(),
+        other => other,
+    }
+}
+
+fn to_snake_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len() + 4);
+    for c in key.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('_');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Drop null fields, which the gRPC output has for every unset optional field
+    fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, without_nulls(value)))
+                .collect(),
+            serde_json::Value::Array(items) => items.into_iter().map(without_nulls).collect(),
+            other => other,
+        }
+    }
+
+    #[test]
+    fn rest_response_matches_grpc_output() {
+        let rest = json!({
+            "nextPageToken": "page-2",
+            "pollingIntervalMillis": 5000,
+            "items": [
+                {
+                    "id": "text-1",
+                    "snippet": {
+                        "type": "textMessageEvent",
+                        "liveChatId": "chat",
+                        "publishedAt": "2024-01-01T00:00:00Z",
+                        "displayMessage": "hello",
+                        "textMessageDetails": { "messageText": "hello" },
+                    },
+                    "authorDetails": { "channelId": "author-1", "isChatModerator": true },
+                },
+                {
+                    "id": "superchat-1",
+                    "snippet": {
+                        "type": "superChatEvent",
+                        "liveChatId": "chat",
+                        "superChatDetails": {
+                            "amountMicros": "5000000",
+                            "currency": "USD",
+                            "tier": 2,
+                        },
+                    },
+                },
+            ],
+        });
+        let grpc: yt_grpc_client::LiveChatMessageListResponse = serde_json::from_value(json!({
+            "next_page_token": "page-2",
+            "polling_interval_millis": 5000,
+            "items": [
+                {
+                    "id": "text-1",
+                    "snippet": {
+                        "type": 1,
+                        "live_chat_id": "chat",
+                        "published_at": "2024-01-01T00:00:00Z",
+                        "display_message": "hello",
+                        "text_message_details": { "message_text": "hello" },
+                    },
+                    "author_details": { "channel_id": "author-1", "is_chat_moderator": true },
+                },
+                {
+                    "id": "superchat-1",
+                    "snippet": {
+                        "type": 15,
+                        "live_chat_id": "chat",
+                        "super_chat_details": {
+                            "amount_micros": 5000000,
+                            "currency": "USD",
+                            "tier": 2,
+                        },
+                    },
+                },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            to_grpc_shape(rest),
+            without_nulls(serde_json::to_value(&grpc).unwrap())
+        );
+    }
+
+    #[test]
+    fn unknown_type_names_are_kept() {
+        let body = to_grpc_shape(json!({
+            "items": [{ "id": "x", "snippet": { "type": "someFutureEvent" } }],
+        }));
+        assert_eq!(body["items"][0]["snippet"]["type"], "someFutureEvent");
+    }
+}
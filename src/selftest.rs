@@ -0,0 +1,215 @@
+use crate::filter::ItemFilter;
+use crate::output::{
+    FlushPolicy, Output, OutputConfig, OutputFormat, OverflowPolicy, RecordDelimiter,
+};
+use crate::stats::Stats;
+use crate::stream::{self, StreamConfig, Transport};
+use std::pin::Pin;
+use std::time::Duration;
+use tokio_stream::Stream;
+use yt_grpc_client::v3_data_live_chat_message_service_server::{
+    V3DataLiveChatMessageService, V3DataLiveChatMessageServiceServer,
+};
+use yt_grpc_client::{LiveChatMessageListRequest, LiveChatMessageListResponse};
+
+const CHAT_ID: &str = "self-test-chat";
+const RESPONSES: usize = 3;
+const ITEMS_PER_RESPONSE: usize = 2;
+
+type MockServer = tokio::task::JoinHandle<Result<(), tonic::transport::Error>>;
+
+/// Serve the mock on a Unix domain socket, so no port has to be free
+#[cfg(unix)]
+async fn spawn_mock_server(
+    socket: &std::path::Path,
+) -> Result<(String, MockServer), Box<dyn std::error::Error>> {
+    let _ = std::fs::remove_file(socket);
+    let listener = tokio::net::UnixListener::bind(socket)?;
+    let server = tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(V3DataLiveChatMessageServiceServer::new(MockService))
+            .serve_with_incoming(tokio_stream::wrappers::UnixListenerStream::new(listener)),
+    );
+    Ok((format!("unix://{}", socket.display()), server))
+}
+
+/// Serve the mock on an ephemeral localhost port
+#[cfg(not(unix))]
+async fn spawn_mock_server(
+    _socket: &std::path::Path,
+) -> Result<(String, MockServer), Box<dyn std::error::Error>> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+    let server_url = format!("http://{}", listener.local_addr()?);
+    let server = tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(V3DataLiveChatMessageServiceServer::new(MockService))
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+    );
+    Ok((server_url, server))
+}
+
+/// Run the fetch pipeline end to end against an in-process mock gRPC server: stream canned
+/// responses, write them to a temporary archive, and check that resume can read it back.
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_path = |extension: &str| {
+        std::env::temp_dir().join(format!(
+            "yt-comment-fetcher-self-test-{}.{}",
+            std::process::id(),
+            extension
+        ))
+    };
+    let socket = temp_path("sock");
+    let (server_url, server) = spawn_mock_server(&socket).await?;
+    log!("Mock gRPC server listening at {}", server_url);
+
+    let path = temp_path("ndjson");
+    let result = check_pipeline(&server_url, &path).await;
+
+    server.abort();
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&socket);
+
+    match result {
+        Ok(()) => {
+            log!("Self-test passed");
+            Ok(())
+        }
+        Err(e) => Err(format!("Self-test failed: {}", e).into()),
+    }
+}
+
+async fn check_pipeline(
+    server_url: &str,
+    path: &std::path::Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path_str = path.to_str().ok_or("temporary path is not valid UTF-8")?;
+    let config = StreamConfig {
+        transport: Transport::Grpc,
+        server_url: server_url.to_string(),
+        rest_api_address: String::new(),
+        api_key: Some("self-test-key".to_string()),
+        access_token_path: None,
+        http_client: reqwest::Client::new(),
+        connect_timeout: Duration::from_secs(5),
+        network: Default::default(),
+        debug_dump: None,
+        client_identity: None,
+        max_message_size: crate::DEFAULT_MAX_MESSAGE_SIZE,
+        api_calls: Default::default(),
+    };
+
+    let mut output = Output::open(OutputConfig {
+        path: Some(path_str.to_string()),
+        tee: false,
+        max_bytes: None,
+        emit_control_events: false,
+        emit_member_events: false,
+        buffer_size: 16,
+        overflow: OverflowPolicy::Block,
+        flush: FlushPolicy::default(),
+        delimiter: RecordDelimiter::Newline,
+        format: OutputFormat::Json,
+    })?;
+    let mut stats = Stats::new();
+    let mut next_page_token = None;
+    let mut filter = ItemFilter::default();
+
+    log!("Streaming from mock server...");
+    let mut stream = stream::open_stream(&config, CHAT_ID, None).await?;
+    while let Some(message) = tokio::time::timeout(Duration::from_secs(5), stream.next())
+        .await
+        .map_err(|_| "timed out waiting for the mock stream")?
+    {
+        crate::process_response(
+            message?,
+            &mut next_page_token,
+            &mut output,
+            &mut stats,
+            &mut filter,
+            &mut [],
+            false,
+        )
+        .await?;
+    }
+    output.shutdown(Duration::from_secs(5)).await;
+
+    let expected_items = (RESPONSES * ITEMS_PER_RESPONSE) as u64;
+    if stats.items != expected_items {
+        return Err(format!("expected {} items, got {}", expected_items, stats.items).into());
+    }
+    let lines = std::fs::read_to_string(path)?.lines().count();
+    if lines != RESPONSES {
+        return Err(format!("expected {} lines in the archive, got {}", RESPONSES, lines).into());
+    }
+    log!("Wrote {} responses ({} items)", lines, stats.items);
+
+    let last_line = crate::read_last_line(path_str, RecordDelimiter::Newline)?
+        .ok_or("archive has no resumable line")?;
+    let (chat_id, page_token) = crate::parse_resume_info(&last_line)?;
+    let expected_token = page_token_for(RESPONSES - 1);
+    if chat_id.as_deref() != Some(CHAT_ID) || page_token.as_deref() != Some(&expected_token) {
+        return Err(format!(
+            "resume parsed chat ID {:?} and page token {:?}, expected {:?} and {:?}",
+            chat_id, page_token, CHAT_ID, expected_token
+        )
+        .into());
+    }
+    log!("Resume info parsed correctly");
+
+    Ok(())
+}
+
+fn page_token_for(response: usize) -> String {
+    format!("self-test-page-{}", response + 1)
+}
+
+/// Canned response built from JSON, so only the fields used here need to exist
+fn canned_response(response: usize) -> Result<LiveChatMessageListResponse, serde_json::Error> {
+    let items: Vec<_> = (0..ITEMS_PER_RESPONSE)
+        .map(|i| {
+            let n = response * ITEMS_PER_RESPONSE + i;
+            serde_json::json!({
+                "id": format!("self-test-message-{}", n),
+                "snippet": {
+                    "type": 1,
+                    "live_chat_id": CHAT_ID,
+                    "published_at": format!("2024-01-01T00:00:{:02}Z", n),
+                    "display_message": format!("self-test message {}", n),
+                },
+                "author_details": {
+                    "channel_id": "self-test-author",
+                    "display_name": "Self Test",
+                },
+            })
+        })
+        .collect();
+    serde_json::from_value(serde_json::json!({
+        "next_page_token": page_token_for(response),
+        "polling_interval_millis": 100,
+        "items": items,
+    }))
+}
+
+struct MockService;
+
+#[tonic::async_trait]
+impl V3DataLiveChatMessageService for MockService {
+    type StreamListStream =
+        Pin<Box<dyn Stream<Item = Result<LiveChatMessageListResponse, tonic::Status>> + Send>>;
+
+    async fn stream_list(
+        &self,
+        request: tonic::Request<LiveChatMessageListRequest>,
+    ) -> Result<tonic::Response<Self::StreamListStream>, tonic::Status> {
+        if request.metadata().get("x-goog-api-key").is_none() {
+            return Err(tonic::Status::unauthenticated("missing API key"));
+        }
+        let responses = (0..RESPONSES)
+            .map(canned_response)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| tonic::Status::internal(e.to_string()))?;
+        Ok(tonic::Response::new(Box::pin(tokio_stream::iter(
+            responses.into_iter().map(Ok),
+        ))))
+    }
+}
@@ -0,0 +1,51 @@
+use serde_json::Value;
+
+/// Numbers written responses for gap detection (`--sequence`).
+///
+/// Each response gets a `seq` that increases by one per record, and a `gap` that increases at
+/// every reconnect (and at every restart with `--resume`), so consumers can tell which records
+/// were received over one continuous connection.
+#[derive(Clone, Copy, Debug)]
+pub struct Sequence {
+    next_seq: u64,
+    gap: u64,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Sequence {
+            next_seq: 1,
+            gap: 0,
+        }
+    }
+}
+
+impl Sequence {
+    /// Continue numbering after the last record of a resumed file. The restart counts as a
+    /// gap, since messages may have been missed while the fetcher wasn't running.
+    pub fn resume_after(last_line: &str) -> Self {
+        let value: Option<Value> = serde_json::from_str(last_line).ok();
+        let number = |name| value.as_ref()?.get(name)?.as_u64();
+        match (number("seq"), number("gap")) {
+            (Some(seq), Some(gap)) => Sequence {
+                next_seq: seq + 1,
+                gap: gap + 1,
+            },
+            _ => Sequence::default(),
+        }
+    }
+
+    /// Add `seq` and `gap` fields to a response about to be written
+    pub fn stamp(&mut self, response: &mut Value) {
+        if let Some(object) = response.as_object_mut() {
+            object.insert("seq".to_string(), self.next_seq.into());
+            object.insert("gap".to_string(), self.gap.into());
+        }
+        self.next_seq += 1;
+    }
+
+    /// Start a new gap after the connection was lost
+    pub fn mark_gap(&mut self) {
+        self.gap += 1;
+    }
+}
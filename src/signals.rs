@@ -0,0 +1,60 @@
+/// A Unix signal to listen for. Never fires on platforms without Unix signals.
+pub struct UnixSignal {
+    #[cfg(unix)]
+    inner: tokio::signal::unix::Signal,
+}
+
+impl UnixSignal {
+    #[cfg(unix)]
+    fn new(kind: tokio::signal::unix::SignalKind) -> std::io::Result<Self> {
+        Ok(UnixSignal {
+            inner: tokio::signal::unix::signal(kind)?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn unavailable() -> Self {
+        UnixSignal {}
+    }
+
+    /// Wait for the signal (never resolves on non-Unix platforms)
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.inner.recv().await;
+
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Process signals handled by the fetch loop besides SIGINT (which uses `tokio::signal::ctrl_c`)
+pub struct Signals {
+    /// Shut down
+    pub sigterm: UnixSignal,
+    /// Reload credentials and chat ID, then reconnect
+    pub sighup: UnixSignal,
+    /// Toggle pausing output
+    pub sigusr1: UnixSignal,
+}
+
+impl Signals {
+    #[cfg(unix)]
+    pub fn new() -> std::io::Result<Self> {
+        use tokio::signal::unix::SignalKind;
+
+        Ok(Signals {
+            sigterm: UnixSignal::new(SignalKind::terminate())?,
+            sighup: UnixSignal::new(SignalKind::hangup())?,
+            sigusr1: UnixSignal::new(SignalKind::user_defined1())?,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Signals {
+            sigterm: UnixSignal::unavailable(),
+            sighup: UnixSignal::unavailable(),
+            sigusr1: UnixSignal::unavailable(),
+        })
+    }
+}
@@ -0,0 +1,31 @@
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// An extra destination for chat responses besides the main output (e.g. a webhook).
+///
+/// Each sink owns its own bounded queue and delivery task, so `offer` never waits: a slow
+/// sink drops and counts what it can't queue instead of stalling the read loop or the other
+/// sinks. The main output is not a `MessageSink` because it may deliberately block
+/// (`--overflow block`) and carries control and header records in order.
+pub trait MessageSink: Send {
+    /// Queue a response (after filtering) for delivery without waiting
+    fn offer(&mut self, response: &Value);
+
+    /// Finish delivering queued data within the grace period and report the sink's metrics
+    fn shutdown(self: Box<Self>, grace: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// Shut down every sink concurrently, so each gets the full grace period
+pub async fn shutdown_all(sinks: Vec<Box<dyn MessageSink>>, grace: Duration) {
+    let handles: Vec<_> = sinks
+        .into_iter()
+        .map(|sink| tokio::spawn(sink.shutdown(grace)))
+        .collect();
+    for handle in handles {
+        if let Err(e) = handle.await {
+            log!("Sink shutdown failed: {}", e);
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::message::{self, Item};
+use crate::sink::MessageSink;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Number of items queued for writing before new ones are dropped, by default
+pub const DEFAULT_QUEUE_SIZE: usize = 1024;
+
+/// File name (without extension) for items whose type can't be read
+const UNKNOWN_TYPE: &str = "unknown";
+
+/// Writes each chat item to `<dir>/<snippet.type>.ndjson`, e.g. `superChatEvent.ndjson`.
+///
+/// Files are opened in append mode as each type first appears, so a resumed run continues
+/// them. Like the other sinks, items are written by a background task and dropped (and
+/// counted) when its queue is full.
+pub struct SplitSink {
+    tx: mpsc::Sender<Value>,
+    task: tokio::task::JoinHandle<()>,
+    dropped: u64,
+}
+
+impl SplitSink {
+    pub fn start(dir: &str, queue_size: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create split directory '{}': {}", dir, e))?;
+        log!("Writing messages split by type to: {}", dir);
+
+        let (tx, rx) = mpsc::channel(queue_size.max(1));
+        let dir = PathBuf::from(dir);
+        let task = tokio::task::spawn_blocking(move || run(dir, rx));
+        Ok(SplitSink {
+            tx,
+            task,
+            dropped: 0,
+        })
+    }
+}
+
+impl MessageSink for SplitSink {
+    /// Queue each item of the response to be written to its type's file
+    fn offer(&mut self, response: &Value) {
+        for item in message::items(response) {
+            if self.tx.try_send(item.clone()).is_err() {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Finish writing queued items, giving up once the grace period has elapsed
+    fn shutdown(self: Box<Self>, grace: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let SplitSink { tx, task, dropped } = *self;
+        Box::pin(async move {
+            drop(tx);
+            if dropped > 0 {
+                log!("Split by type: {} items dropped (queue full)", dropped);
+            }
+            if tokio::time::timeout(grace, task).await.is_err() {
+                log!(
+                    "Split by type files were not flushed within {} seconds, giving up",
+                    grace.as_secs()
+                );
+            }
+        })
+    }
+}
+
+fn run(dir: PathBuf, mut rx: mpsc::Receiver<Value>) {
+    let mut files: HashMap<String, BufWriter<File>> = HashMap::new();
+    let mut written = 0u64;
+    let mut failed = 0u64;
+
+    while let Some(item) = rx.blocking_recv() {
+        let name = file_name(Item(&item).message_type().as_deref());
+        let result = match files.get_mut(&name) {
+            Some(file) => write_item(file, &item),
+            None => {
+                let path = dir.join(format!("{}.ndjson", name));
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .map_err(|e| {
+                        std::io::Error::new(
+                            e.kind(),
+                            format!("failed to open '{}': {}", path.display(), e),
+                        )
+                    })
+                    .and_then(|file| {
+                        let file = files.entry(name).or_insert(BufWriter::new(file));
+                        write_item(file, &item)
+                    })
+            }
+        };
+        match result {
+            Ok(()) => written += 1,
+            Err(e) => {
+                log!("Split by type: failed to write item: {}", e);
+                failed += 1;
+            }
+        }
+    }
+
+    log!(
+        "Split by type: {} items written to {} files, {} failed",
+        written,
+        files.len(),
+        failed
+    );
+}
+
+/// Write one item as a line, flushed right away like the main output
+fn write_item(file: &mut BufWriter<File>, item: &Value) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *file, item)?;
+    file.write_all(b"\n")?;
+    file.flush()
+}
+
+/// A safe file name for a message type; anything but letters, digits, `-` and `_` is replaced
+fn file_name(message_type: Option<&str>) -> String {
+    match message_type {
+        Some(name) if !name.is_empty() => name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect(),
+        _ => UNKNOWN_TYPE.to_string(),
+    }
+}
@@ -0,0 +1,80 @@
+use crate::quota::ApiCalls;
+use crate::sequence::Sequence;
+use crate::superchat::SuperChatTally;
+use std::time::{Instant, SystemTime};
+
+/// Counters collected while streaming, reported to stderr on shutdown
+#[derive(Debug)]
+pub struct Stats {
+    started_at: Instant,
+    /// Number of responses received from the server (including empty ones)
+    pub responses: u64,
+    /// Number of chat items written to the output
+    pub items: u64,
+    /// Number of records dropped because the output writer couldn't keep up
+    pub dropped: u64,
+    /// Number of reconnection attempts
+    pub reconnects: u64,
+    /// Most recent polling interval advertised by the server
+    pub polling_interval_millis: Option<u64>,
+    /// When the last response (including empty ones) was received
+    pub last_response_at: Option<SystemTime>,
+    /// When the last response containing chat items was received
+    pub last_message_at: Option<SystemTime>,
+    /// Paid message totals, when `--superchat-summary` is enabled
+    pub super_chats: Option<SuperChatTally>,
+    /// Sequence numbering of written responses, when `--sequence` is enabled
+    pub sequence: Option<Sequence>,
+    /// API calls made so far, for the quota estimate
+    pub api_calls: ApiCalls,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats {
+            started_at: Instant::now(),
+            responses: 0,
+            items: 0,
+            dropped: 0,
+            reconnects: 0,
+            polling_interval_millis: None,
+            last_response_at: None,
+            last_message_at: None,
+            super_chats: None,
+            sequence: None,
+            api_calls: ApiCalls::default(),
+        }
+    }
+
+    /// Record the server-provided polling interval, returning true if it changed
+    pub fn record_polling_interval(&mut self, millis: Option<u64>) -> bool {
+        if millis.is_none() || millis == self.polling_interval_millis {
+            return false;
+        }
+        self.polling_interval_millis = millis;
+        true
+    }
+
+    /// Print a one-line summary of the run to stderr
+    pub fn print_summary(&self) {
+        let polling = match self.polling_interval_millis {
+            Some(ms) => format!("{}ms", ms),
+            None => "n/a".to_string(),
+        };
+        log!(
+            "Summary: {} responses, {} items, {} dropped, {} reconnects, polling interval {}, elapsed {}s",
+            self.responses,
+            self.items,
+            self.dropped,
+            self.reconnects,
+            polling,
+            self.started_at.elapsed().as_secs()
+        );
+
+        log!("API usage: {}", self.api_calls.summary());
+
+        if let Some(ref super_chats) = self.super_chats {
+            super_chats.print_summary();
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::stats::Stats;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How often the status file is rewritten
+pub const STATUS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Small JSON status file for external supervisors, replaced atomically on each write
+pub struct StatusFile {
+    path: String,
+}
+
+impl StatusFile {
+    pub fn new(path: String) -> Self {
+        log!("Status file: {}", path);
+        StatusFile { path }
+    }
+
+    /// Write the current status to a temporary file, then rename it over the status file
+    pub fn write(&self, connected: bool, chat_id: &str, stats: &Stats) -> std::io::Result<()> {
+        let status = serde_json::json!({
+            "pid": std::process::id(),
+            "connected": connected,
+            "chat_id": chat_id,
+            "messages": stats.items,
+            "responses": stats.responses,
+            "api_calls": stats.api_calls.total(),
+            "quota_units": stats.api_calls.quota_units(),
+            "last_message_at": stats.last_message_at.map(unix_secs),
+            "last_response_at": stats.last_response_at.map(unix_secs),
+            "updated_at": unix_secs(SystemTime::now()),
+        });
+
+        let tmp_path = format!("{}.tmp", self.path);
+        std::fs::write(&tmp_path, status.to_string())?;
+        std::fs::rename(&tmp_path, &self.path)
+    }
+
+    /// Remove the status file (called on clean shutdown)
+    pub fn remove(&self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log!("Failed to remove status file '{}': {}", self.path, e);
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
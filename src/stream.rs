@@ -0,0 +1,235 @@
+use crate::debug_dump::DebugDump;
+use crate::quota::{ApiCalls, Endpoint};
+use crate::rest::LiveChatPoller;
+use tokio_stream::StreamExt;
+use yt_grpc_client::{LiveChatMessageListResponse, YouTubeClient};
+
+/// Transport used to receive live chat messages
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// Server-streaming gRPC (streamList)
+    Grpc,
+    /// Polling the REST liveChatMessages.list endpoint
+    Rest,
+}
+
+/// Addresses and credentials needed to (re)open a chat stream
+pub struct StreamConfig {
+    pub transport: Transport,
+    pub server_url: String,
+    pub rest_api_address: String,
+    pub api_key: Option<String>,
+    /// File holding an OAuth access token for videos.list and the gRPC stream, re-read on
+    /// every lookup and connect
+    pub access_token_path: Option<String>,
+    /// HTTP client shared by all REST calls
+    pub http_client: reqwest::Client,
+    /// Limit on connecting and receiving the first response when opening a stream
+    pub connect_timeout: std::time::Duration,
+    /// IPv4 preference and DNS/connect limits for gRPC connections
+    pub network: yt_grpc_client::NetworkOptions,
+    /// Where to write details of failed requests (`--debug-dump-dir`)
+    pub debug_dump: Option<DebugDump>,
+    /// Client certificate for gRPC servers that require mutual TLS
+    pub client_identity: Option<tonic::transport::Identity>,
+    /// Largest gRPC response accepted, in bytes
+    pub max_message_size: usize,
+    /// Counts API calls for the quota estimate
+    pub api_calls: ApiCalls,
+}
+
+/// A stream of live chat responses, independent of the underlying transport
+pub enum ChatStream {
+    Grpc {
+        stream: Box<tonic::Streaming<LiveChatMessageListResponse>>,
+        debug_dump: Option<DebugDump>,
+    },
+    Rest(LiveChatPoller),
+}
+
+impl ChatStream {
+    /// Wait for the next response as JSON. Returns None when the stream has ended.
+    pub async fn next(&mut self) -> Option<Result<serde_json::Value, Box<dyn std::error::Error>>> {
+        match self {
+            ChatStream::Grpc { stream, debug_dump } => match stream.next().await? {
+                Ok(message) => Some(serde_json::to_value(&message).map_err(Into::into)),
+                Err(status) => {
+                    if is_message_too_large(&status) {
+                        log!(
+                            "A response exceeded the gRPC message size limit; raise it with --max-message-size"
+                        );
+                    }
+                    if let Some(dump) = debug_dump {
+                        dump.grpc_status("grpc-stream", &status);
+                    }
+                    Some(Err(status.into()))
+                }
+            },
+            ChatStream::Rest(poller) => poller.next().await,
+        }
+    }
+}
+
+/// Number of stream ends within `STREAM_END_WINDOW` that triggers the longer cooldown
+pub const STREAM_END_LIMIT: usize = 5;
+
+/// Window in which repeated stream ends are counted
+pub const STREAM_END_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Tracks recent stream ends to detect a reconnect loop on a stream that closes immediately
+pub struct StreamEnds {
+    recent: std::collections::VecDeque<tokio::time::Instant>,
+    /// Wait used instead of the reconnect wait once the limit is reached
+    pub cooldown: std::time::Duration,
+}
+
+impl StreamEnds {
+    pub fn new(cooldown: std::time::Duration) -> Self {
+        StreamEnds {
+            recent: std::collections::VecDeque::with_capacity(STREAM_END_LIMIT),
+            cooldown,
+        }
+    }
+
+    /// Record a stream end, returning true when it is the `STREAM_END_LIMIT`th within
+    /// `STREAM_END_WINDOW` (the count then starts over)
+    pub fn record(&mut self, now: tokio::time::Instant) -> bool {
+        while self
+            .recent
+            .front()
+            .is_some_and(|&end| now.duration_since(end) > STREAM_END_WINDOW)
+        {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(now);
+        if self.recent.len() >= STREAM_END_LIMIT {
+            self.recent.clear();
+            return true;
+        }
+        false
+    }
+}
+
+/// Whether opening a stream failed because the chat ID is unknown or no longer valid, e.g.
+/// after a premiere turned into a live stream with a new chat
+pub fn is_chat_not_found(error: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(status) = error.downcast_ref::<tonic::Status>() {
+        return matches!(
+            status.code(),
+            tonic::Code::NotFound | tonic::Code::InvalidArgument
+        );
+    }
+    if let Some(error) = error.downcast_ref::<crate::rest::ChatMessagesError>() {
+        return error.status == reqwest::StatusCode::NOT_FOUND
+            || error.body.contains("liveChatNotFound");
+    }
+    false
+}
+
+/// Whether the stream was rejected because its page token is invalid or has expired
+pub fn is_page_token_invalid(error: &(dyn std::error::Error + 'static)) -> bool {
+    if let Some(status) = error.downcast_ref::<tonic::Status>() {
+        return status.code() == tonic::Code::InvalidArgument
+            && (status.message().contains("pageTokenInvalid")
+                || status.message().to_lowercase().contains("page token"));
+    }
+    if let Some(error) = error.downcast_ref::<crate::rest::ChatMessagesError>() {
+        return error.body.contains("pageTokenInvalid");
+    }
+    false
+}
+
+/// Whether tonic rejected a response for exceeding the decoding size limit
+fn is_message_too_large(status: &tonic::Status) -> bool {
+    status.code() == tonic::Code::OutOfRange
+        && status.message().contains("message length too large")
+}
+
+/// Open a chat stream for the given chat ID, starting from the page token if provided.
+///
+/// Fails if the connection can't be established within the configured connect timeout.
+pub async fn open_stream(
+    config: &StreamConfig,
+    chat_id: &str,
+    page_token: Option<String>,
+) -> Result<ChatStream, Box<dyn std::error::Error>> {
+    tokio::time::timeout(
+        config.connect_timeout,
+        open_stream_unbounded(config, chat_id, page_token),
+    )
+    .await
+    .map_err(|_| {
+        format!(
+            "Timed out connecting after {} seconds",
+            config.connect_timeout.as_secs()
+        )
+    })?
+}
+
+async fn open_stream_unbounded(
+    config: &StreamConfig,
+    chat_id: &str,
+    page_token: Option<String>,
+) -> Result<ChatStream, Box<dyn std::error::Error>> {
+    match config.transport {
+        Transport::Grpc => {
+            let opened = async {
+                // Re-read on every connect, so a token refreshed by a helper is picked up
+                let access_token = config
+                    .access_token_path
+                    .as_deref()
+                    .map(crate::read_access_token)
+                    .transpose()?;
+                let mut builder = YouTubeClient::builder(config.server_url.clone())
+                    .api_key(config.api_key.clone())
+                    .access_token(access_token)
+                    .max_decoding_message_size(config.max_message_size)
+                    .network(config.network);
+                if let Some(ref identity) = config.client_identity {
+                    builder = builder.client_identity(identity.clone());
+                }
+                let mut client = builder.connect().await?;
+                config.api_calls.record(Endpoint::LiveChatStream);
+                client
+                    .stream_comments(Some(chat_id.to_string()), page_token)
+                    .await
+            }
+            .await;
+            match opened {
+                Ok(stream) => Ok(ChatStream::Grpc {
+                    stream: Box::new(stream),
+                    debug_dump: config.debug_dump.clone(),
+                }),
+                Err(e) => {
+                    if let Some(ref dump) = config.debug_dump {
+                        match e.downcast_ref::<tonic::Status>() {
+                            Some(status) => dump.grpc_status("grpc-open", status),
+                            None => dump.error("grpc-open", e.as_ref()),
+                        }
+                    }
+                    Err(e)
+                }
+            }
+        }
+        Transport::Rest => {
+            // Re-read on every connect, as for gRPC
+            let access_token = config
+                .access_token_path
+                .as_deref()
+                .map(crate::read_access_token)
+                .transpose()?;
+            let mut poller = LiveChatPoller::new(
+                config.http_client.clone(),
+                config.rest_api_address.clone(),
+                config.api_key.clone(),
+                chat_id.to_string(),
+                page_token,
+                config.debug_dump.clone(),
+                config.api_calls.clone(),
+            )
+            .access_token(access_token);
+            poller.prime().await?;
+            Ok(ChatStream::Rest(poller))
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::message::{self, Item};
+use std::collections::{BTreeMap, HashMap};
+
+/// Number of contributors listed per currency in the summary
+const TOP_CONTRIBUTORS: usize = 5;
+
+/// Per-currency totals of Super Chats and Super Stickers.
+///
+/// Amounts are kept as integer micros so totals are exact.
+#[derive(Debug, Default)]
+pub struct SuperChatTally {
+    currencies: BTreeMap<String, CurrencyTally>,
+}
+
+#[derive(Debug, Default)]
+struct CurrencyTally {
+    count: u64,
+    total_micros: u64,
+    /// Totals by author channel ID
+    contributors: HashMap<String, Contributor>,
+}
+
+#[derive(Debug, Default)]
+struct Contributor {
+    name: String,
+    total_micros: u64,
+}
+
+impl SuperChatTally {
+    /// Count a chat item if it is a paid message
+    pub fn record(&mut self, item: Item) {
+        let (Some(micros), Some(currency)) = (item.amount_micros(), item.currency()) else {
+            return;
+        };
+
+        let tally = self.currencies.entry(currency.to_string()).or_default();
+        tally.count += 1;
+        tally.total_micros += micros;
+
+        let channel_id = item.author_channel_id().unwrap_or_default();
+        let contributor = tally
+            .contributors
+            .entry(channel_id.to_string())
+            .or_default();
+        contributor.total_micros += micros;
+        if let Some(name) = item.author_name() {
+            contributor.name = name.to_string();
+        }
+    }
+
+    /// Print per-currency totals and top contributors to stderr
+    pub fn print_summary(&self) {
+        if self.currencies.is_empty() {
+            log!("Super chats: none");
+            return;
+        }
+
+        log!("Super chats:");
+        for (currency, tally) in &self.currencies {
+            log!(
+                "  {} {} ({} paid messages)",
+                currency,
+                message::format_micros(tally.total_micros),
+                tally.count
+            );
+
+            let mut contributors: Vec<_> = tally.contributors.iter().collect();
+            contributors.sort_by(|(a_id, a), (b_id, b)| {
+                b.total_micros.cmp(&a.total_micros).then(a_id.cmp(b_id))
+            });
+            for (channel_id, contributor) in contributors.into_iter().take(TOP_CONTRIBUTORS) {
+                log!(
+                    "    {} ({}): {}",
+                    contributor.name,
+                    channel_id,
+                    message::format_micros(contributor.total_micros)
+                );
+            }
+        }
+    }
+}
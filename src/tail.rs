@@ -0,0 +1,170 @@
+use crate::output::{
+    FlushPolicy, Output, OutputConfig, OutputFormat, OverflowPolicy, RecordDelimiter,
+};
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
+use std::time::Duration;
+
+/// How often the file is checked for new data, truncation and rotation
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Grace period for flushing stdout once following stops
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Follow an archive written by another fetcher instance and copy each new complete record
+/// to stdout with the same delimiter, like `tail -F`.
+///
+/// The file is reopened from the start when it is truncated or replaced (rotated), and waited
+/// for if it doesn't exist yet. Runs until Ctrl+C.
+pub async fn follow(
+    path: &str,
+    from_start: bool,
+    delimiter: RecordDelimiter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut output = Output::open(OutputConfig {
+        path: None,
+        tee: false,
+        max_bytes: None,
+        emit_control_events: true,
+        emit_member_events: false,
+        buffer_size: 1024,
+        overflow: OverflowPolicy::Block,
+        flush: FlushPolicy::default(),
+        delimiter,
+        format: OutputFormat::Json,
+    })?;
+
+    log!("Following: {}", path);
+    let mut follower = Follower::new(path, from_start, delimiter);
+    let mut lines = 0u64;
+
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        for line in follower.read_lines()? {
+            output.write_record(line).await?;
+            lines += 1;
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = &mut ctrl_c => {
+                log!("Received Ctrl+C, stopping");
+                break;
+            }
+        }
+    }
+
+    output.shutdown(SHUTDOWN_GRACE).await;
+    log!("Followed {} lines", lines);
+    Ok(())
+}
+
+struct Follower {
+    path: String,
+    file: Option<File>,
+    /// Read position in the open file
+    position: u64,
+    /// Start at the end of the first file opened (later files are always read from the start)
+    skip_existing: bool,
+    /// Bytes of an incomplete last record, held back until its delimiter arrives
+    partial: Vec<u8>,
+    delimiter: u8,
+}
+
+impl Follower {
+    fn new(path: &str, from_start: bool, delimiter: RecordDelimiter) -> Self {
+        Follower {
+            path: path.to_string(),
+            file: None,
+            position: 0,
+            skip_existing: !from_start,
+            partial: Vec::new(),
+            delimiter: delimiter.byte(),
+        }
+    }
+
+    /// Read whatever complete records have been appended since the last call
+    fn read_lines(&mut self) -> std::io::Result<Vec<String>> {
+        let current = match std::fs::metadata(&self.path) {
+            Ok(metadata) => Some(metadata),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        if let Some(ref file) = self.file {
+            let opened = file.metadata()?;
+            let replaced = current
+                .as_ref()
+                .is_some_and(|current| !same_file(&opened, current));
+            if replaced {
+                log!("{} was replaced, reopening", self.path);
+                // Pick up anything written to the old file before it was rotated away
+                let mut lines = self.read_appended()?;
+                self.file = None;
+                lines.extend(self.open_and_read(current)?);
+                return Ok(lines);
+            }
+            if opened.len() < self.position {
+                log!("{} was truncated, reading from the start", self.path);
+                self.position = 0;
+                self.partial.clear();
+            }
+            return self.read_appended();
+        }
+
+        self.open_and_read(current)
+    }
+
+    fn open_and_read(&mut self, current: Option<Metadata>) -> std::io::Result<Vec<String>> {
+        if current.is_none() {
+            return Ok(Vec::new());
+        }
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        self.position = if std::mem::take(&mut self.skip_existing) {
+            file.metadata()?.len()
+        } else {
+            0
+        };
+        self.partial.clear();
+        self.file = Some(file);
+        self.read_appended()
+    }
+
+    fn read_appended(&mut self) -> std::io::Result<Vec<String>> {
+        let Some(ref mut file) = self.file else {
+            return Ok(Vec::new());
+        };
+        file.seek(SeekFrom::Start(self.position))?;
+        let mut appended = Vec::new();
+        self.position += file.read_to_end(&mut appended)? as u64;
+        self.partial.extend_from_slice(&appended);
+
+        let Some(end) = self.partial.iter().rposition(|&b| b == self.delimiter) else {
+            return Ok(Vec::new());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+        Ok(complete
+            .split(|&b| b == self.delimiter)
+            .map(String::from_utf8_lossy)
+            .filter(|record| !record.trim().is_empty())
+            .map(|record| record.into_owned())
+            .collect())
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Without inode numbers only truncation can be detected
+#[cfg(not(unix))]
+fn same_file(_a: &Metadata, _b: &Metadata) -> bool {
+    true
+}
@@ -0,0 +1,314 @@
+//! Runs the fetcher against a scripted mock of the gRPC service to check reconnect, resume
+//! and backoff behaviour.
+
+mod support;
+
+use support::{Call, MockServer, archived_ids, response, run_fetcher, temp_path};
+
+const CHAT_ID: &str = "mock-chat";
+
+/// An archive to resume from, so no REST lookup of the chat ID is needed
+fn seed_archive(name: &str, next_page_token: &str) -> std::path::PathBuf {
+    let path = temp_path(name);
+    let seed = serde_json::to_string(&response(CHAT_ID, &["seed"], next_page_token)).unwrap();
+    std::fs::write(&path, format!("{}\n", seed)).unwrap();
+    path
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resume_continues_from_archived_page_token() {
+    let server = MockServer::start(vec![Call::Stream(vec![Ok(response(
+        CHAT_ID,
+        &["a"],
+        "page-2",
+    ))])])
+    .await;
+    let archive = seed_archive("resume.ndjson", "page-1");
+
+    let output = run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--reconnect-policy",
+            "none",
+        ],
+    )
+    .await;
+
+    let received = server.received();
+    assert_eq!(received[0].live_chat_id.as_deref(), Some(CHAT_ID));
+    assert_eq!(received[0].page_token.as_deref(), Some("page-1"));
+    assert_eq!(archived_ids(&archive), ["seed", "a"]);
+    assert!(!output.status.success(), "gives up once the stream ends");
+    let _ = std::fs::remove_file(archive);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reconnects_from_last_page_token() {
+    let server = MockServer::start(vec![
+        Call::Stream(vec![
+            Ok(response(CHAT_ID, &["a"], "page-2")),
+            Ok(response(CHAT_ID, &["b"], "page-3")),
+        ]),
+        Call::Stream(vec![
+            Ok(response(CHAT_ID, &["c"], "page-4")),
+            Err(tonic::Status::internal("connection reset")),
+        ]),
+        Call::Stream(vec![Ok(response(CHAT_ID, &["d"], "page-5"))]),
+    ])
+    .await;
+    let archive = seed_archive("reconnect.ndjson", "page-1");
+
+    run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--reconnect-wait-secs",
+            "0",
+            "--max-duration",
+            "3s",
+        ],
+    )
+    .await;
+
+    let tokens: Vec<_> = server
+        .received()
+        .into_iter()
+        .map(|call| call.page_token)
+        .collect();
+    assert_eq!(
+        tokens[..3],
+        [
+            Some("page-1".to_string()),
+            Some("page-3".to_string()),
+            Some("page-4".to_string())
+        ]
+    );
+    assert_eq!(archived_ids(&archive), ["seed", "a", "b", "c", "d"]);
+    let _ = std::fs::remove_file(archive);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn exponential_policy_backs_off_between_failed_attempts() {
+    let server = MockServer::start(vec![
+        Call::Stream(vec![Ok(response(CHAT_ID, &["a"], "page-2"))]),
+        Call::Reject(tonic::Status::internal("backend error")),
+        Call::Reject(tonic::Status::internal("backend error")),
+    ])
+    .await;
+    let archive = seed_archive("backoff.ndjson", "page-1");
+
+    run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--reconnect-policy",
+            "exponential",
+            "--reconnect-wait-secs",
+            "1",
+            "--max-duration",
+            "4s",
+        ],
+    )
+    .await;
+
+    let received = server.received();
+    assert_eq!(received.len(), 3, "calls: {:?}", received);
+    let first_wait = received[1].at - received[0].at;
+    let second_wait = received[2].at - received[1].at;
+    assert!(first_wait.as_millis() >= 900, "first wait {:?}", first_wait);
+    assert!(
+        second_wait.as_millis() >= 1900,
+        "second wait {:?}",
+        second_wait
+    );
+    let _ = std::fs::remove_file(archive);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn end_record_reports_reconnect_give_up() {
+    let server = MockServer::start(vec![Call::Stream(vec![Err(tonic::Status::internal(
+        "backend error",
+    ))])])
+    .await;
+    let archive = seed_archive("give-up.ndjson", "page-1");
+
+    let output = run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--reconnect-policy",
+            "none",
+            "--emit-control-events",
+        ],
+    )
+    .await;
+
+    assert!(!output.status.success());
+    let last_line = std::fs::read_to_string(&archive)
+        .unwrap()
+        .lines()
+        .last()
+        .map(str::to_string)
+        .unwrap();
+    let end: serde_json::Value = serde_json::from_str(&last_line).unwrap();
+    assert_eq!(end["event"], "end");
+    assert_eq!(end["reason"], "reconnect_gave_up");
+    let _ = std::fs::remove_file(archive);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn strict_mode_stops_before_writing_a_malformed_response() {
+    let mut malformed = response(CHAT_ID, &["b"], "page-3");
+    malformed.items[0].id = None;
+    let server = MockServer::start(vec![Call::Stream(vec![
+        Ok(response(CHAT_ID, &["a"], "page-2")),
+        Ok(malformed),
+    ])])
+    .await;
+    let archive = seed_archive("strict.ndjson", "page-1");
+
+    let output = run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--strict",
+            "--json-errors",
+        ],
+    )
+    .await;
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(r#""code":"malformed_response""#),
+        "{}",
+        stderr
+    );
+    assert_eq!(archived_ids(&archive), ["seed", "a"]);
+    let _ = std::fs::remove_file(archive);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streams_over_tls() {
+    let server = MockServer::start_tls(vec![Call::Stream(vec![Ok(response(
+        CHAT_ID,
+        &["a"],
+        "page-2",
+    ))])])
+    .await;
+    let archive = seed_archive("tls.ndjson", "page-1");
+
+    let output = run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--reconnect-policy",
+            "none",
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        server.received().len(),
+        1,
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(archived_ids(&archive), ["seed", "a"]);
+    let _ = std::fs::remove_file(archive);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streams_over_tls_with_custom_resolver() {
+    let server = MockServer::start_tls(vec![Call::Stream(vec![Ok(response(
+        CHAT_ID,
+        &["a"],
+        "page-2",
+    ))])])
+    .await;
+    let archive = seed_archive("tls-resolver.ndjson", "page-1");
+
+    let output = run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--reconnect-policy",
+            "none",
+            "--prefer-ipv4",
+            "--dns-timeout-secs",
+            "5",
+        ],
+    )
+    .await;
+
+    assert_eq!(
+        server.received().len(),
+        1,
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(archived_ids(&archive), ["seed", "a"]);
+    let _ = std::fs::remove_file(archive);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn rejected_credentials_end_the_run_after_one_refresh() {
+    let server = MockServer::start(vec![
+        Call::Stream(vec![
+            Ok(response(CHAT_ID, &["a"], "page-2")),
+            Err(tonic::Status::unauthenticated("token expired")),
+        ]),
+        Call::Reject(tonic::Status::unauthenticated("token expired")),
+        Call::Stream(vec![Ok(response(CHAT_ID, &["b"], "page-3"))]),
+    ])
+    .await;
+    let archive = seed_archive("unauthenticated.ndjson", "page-1");
+    let token = temp_path("token.txt");
+    std::fs::write(&token, "expired-token\n").unwrap();
+
+    let output = run_fetcher(
+        &server,
+        &[
+            "--resume",
+            "--output-file",
+            archive.to_str().unwrap(),
+            "--access-token-path",
+            token.to_str().unwrap(),
+            "--emit-control-events",
+            "--json-errors",
+            "--max-duration",
+            "10s",
+        ],
+    )
+    .await;
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(r#""code":"unauthorized""#), "{}", stderr);
+    assert_eq!(server.received().len(), 2, "no further reconnects");
+    let last_line = std::fs::read_to_string(&archive)
+        .unwrap()
+        .lines()
+        .last()
+        .map(str::to_string)
+        .unwrap();
+    let end: serde_json::Value = serde_json::from_str(&last_line).unwrap();
+    assert_eq!(end["reason"], "unauthorized");
+    let _ = std::fs::remove_file(archive);
+    let _ = std::fs::remove_file(token);
+}
@@ -23,3 +23,17 @@ Tags: reconnection, pagination
 * Verify fetcher received additional messages with correct pagination
 * Stop the fetcher application
 
+
+## Test fetcher reconnects immediately with a zero reconnect wait
+
+Tags: reconnection
+
+* Start the fetcher application with reconnect wait time "0" seconds
+* Wait for fetcher to connect and receive messages
+* Record the current message count
+* Wait for stream timeout to occur
+* Verify fetcher reconnected within "1000" milliseconds of the stream ending
+* Add "3" new messages via mock control endpoint
+* Wait for fetcher to receive new messages
+* Verify fetcher received additional messages with correct pagination
+* Stop the fetcher application
@@ -0,0 +1,223 @@
+//! Test support: a scriptable mock of the live chat gRPC service, and helpers to run the
+//! fetcher binary against it.
+
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::Output;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_stream::Stream;
+use yt_grpc_client::v3_data_live_chat_message_service_server::{
+    V3DataLiveChatMessageService, V3DataLiveChatMessageServiceServer,
+};
+use yt_grpc_client::{LiveChatMessageListRequest, LiveChatMessageListResponse};
+
+/// How the server answers one `StreamList` call
+pub enum Call {
+    /// Send these responses in order, then end the stream cleanly. An `Err` ends the stream
+    /// with that status instead.
+    Stream(Vec<Result<LiveChatMessageListResponse, tonic::Status>>),
+    /// Refuse the call with this status
+    Reject(tonic::Status),
+}
+
+/// A `StreamList` call as the server received it
+#[derive(Clone, Debug)]
+pub struct Received {
+    pub live_chat_id: Option<String>,
+    pub page_token: Option<String>,
+    pub at: Instant,
+}
+
+type Script = Arc<Mutex<std::collections::VecDeque<Call>>>;
+
+/// Mock gRPC server answering calls from a script, one [`Call`] per connection in order.
+/// Calls past the end of the script are rejected with UNAVAILABLE.
+pub struct MockServer {
+    /// Address to pass as SERVER_ADDRESS
+    pub url: String,
+    /// PEM file of the CA that signed the server certificate, when serving TLS
+    ca_file: Option<PathBuf>,
+    received: Arc<Mutex<Vec<Received>>>,
+    server: tokio::task::JoinHandle<Result<(), tonic::transport::Error>>,
+}
+
+impl MockServer {
+    /// Serve the script on an ephemeral localhost port
+    pub async fn start(script: Vec<Call>) -> MockServer {
+        Self::serve(script, false).await
+    }
+
+    /// Serve the script over TLS, with a certificate for 127.0.0.1 signed by a throwaway CA
+    /// that [`run_fetcher`] tells the fetcher to trust
+    pub async fn start_tls(script: Vec<Call>) -> MockServer {
+        Self::serve(script, true).await
+    }
+
+    async fn serve(script: Vec<Call>, tls: bool) -> MockServer {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind mock server");
+        let addr = listener.local_addr().unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let service = MockService {
+            script: Arc::new(Mutex::new(script.into())),
+            received: received.clone(),
+        };
+        let mut builder = tonic::transport::Server::builder();
+        let (url, ca_file) = if tls {
+            let (identity, ca_pem) = server_identity();
+            let ca_file = temp_path(&format!("ca-{}.pem", addr.port()));
+            std::fs::write(&ca_file, ca_pem).unwrap();
+            builder = builder
+                .tls_config(tonic::transport::ServerTlsConfig::new().identity(identity))
+                .expect("server TLS config");
+            (format!("https://{}", addr), Some(ca_file))
+        } else {
+            (format!("http://{}", addr), None)
+        };
+        let server = tokio::spawn(
+            builder
+                .add_service(V3DataLiveChatMessageServiceServer::new(service))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+        MockServer {
+            url,
+            ca_file,
+            received,
+            server,
+        }
+    }
+
+    /// The calls received so far
+    pub fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.server.abort();
+        if let Some(ca_file) = &self.ca_file {
+            let _ = std::fs::remove_file(ca_file);
+        }
+    }
+}
+
+/// A server certificate for 127.0.0.1 and the PEM of the CA that signed it
+fn server_identity() -> (tonic::transport::Identity, String) {
+    let ca_key = rcgen::KeyPair::generate().unwrap();
+    let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let key = rcgen::KeyPair::generate().unwrap();
+    let cert = rcgen::CertificateParams::new(vec!["127.0.0.1".to_string()])
+        .unwrap()
+        .signed_by(&key, &ca, &ca_key)
+        .unwrap();
+    (
+        tonic::transport::Identity::from_pem(cert.pem(), key.serialize_pem()),
+        ca.pem(),
+    )
+}
+
+struct MockService {
+    script: Script,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+#[tonic::async_trait]
+impl V3DataLiveChatMessageService for MockService {
+    type StreamListStream =
+        Pin<Box<dyn Stream<Item = Result<LiveChatMessageListResponse, tonic::Status>> + Send>>;
+
+    async fn stream_list(
+        &self,
+        request: tonic::Request<LiveChatMessageListRequest>,
+    ) -> Result<tonic::Response<Self::StreamListStream>, tonic::Status> {
+        let request = request.into_inner();
+        self.received.lock().unwrap().push(Received {
+            live_chat_id: request.live_chat_id,
+            page_token: request.page_token,
+            at: Instant::now(),
+        });
+        let call = self.script.lock().unwrap().pop_front();
+        match call {
+            Some(Call::Stream(responses)) => Ok(tonic::Response::new(Box::pin(
+                tokio_stream::iter(responses),
+            ))),
+            Some(Call::Reject(status)) => Err(status),
+            None => Err(tonic::Status::unavailable("mock script exhausted")),
+        }
+    }
+}
+
+/// A response holding `ids.len()` text messages in `chat_id`, pointing at `next_page_token`
+pub fn response(chat_id: &str, ids: &[&str], next_page_token: &str) -> LiveChatMessageListResponse {
+    let items: Vec<_> = ids
+        .iter()
+        .map(|id| {
+            serde_json::json!({
+                "id": id,
+                "snippet": {
+                    "type": 1,
+                    "live_chat_id": chat_id,
+                    "published_at": "2024-01-01T00:00:00Z",
+                    "display_message": format!("message {}", id),
+                },
+                "author_details": {
+                    "channel_id": format!("author-{}", id),
+                    "display_name": "Test",
+                },
+            })
+        })
+        .collect();
+    serde_json::from_value(serde_json::json!({
+        "next_page_token": next_page_token,
+        "polling_interval_millis": 100,
+        "items": items,
+    }))
+    .expect("valid response")
+}
+
+/// A path in the temp directory unique to this test process and `name`
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "yt-comment-fetcher-test-{}-{}",
+        std::process::id(),
+        name
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Run the fetcher against `server` with the given arguments, waiting for it to exit
+pub async fn run_fetcher(server: &MockServer, args: &[&str]) -> Output {
+    let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_yt-comment-fetcher"));
+    command.args(args).env("SERVER_ADDRESS", &server.url);
+    if let Some(ca_file) = &server.ca_file {
+        // Read by the system trust store loader in place of the platform's certificates
+        command.env("SSL_CERT_FILE", ca_file);
+    }
+    tokio::task::spawn_blocking(move || command.output().expect("run yt-comment-fetcher"))
+        .await
+        .unwrap()
+}
+
+/// The message IDs in an NDJSON archive of responses, in order
+pub fn archived_ids(path: &std::path::Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .flat_map(|value| {
+            value["items"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|item| item["id"].as_str().map(str::to_string))
+        })
+        .collect()
+}
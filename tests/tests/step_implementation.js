@@ -231,6 +231,16 @@ async function startFetcherWithOptions(options = {}) {
       errorOutput += output;
       getStore().put('stderrOutput', stderrOutput);
       getStore().put('errorOutput', errorOutput);
+
+      // Note when the stream was first lost and first re-established, to check reconnect delays
+      if (!getStore().get('streamEndedAt') &&
+          (output.includes('Stream ended') || output.includes('Connection lost'))) {
+        getStore().put('streamEndedAt', Date.now());
+      }
+      if (getStore().get('streamEndedAt') && !getStore().get('reconnectedAt') &&
+          output.includes('Reconnected successfully')) {
+        getStore().put('reconnectedAt', Date.now());
+      }
       
       // For output file mode, resolve on connection message
       if (outputFile && startupTimeout) {
@@ -710,6 +720,24 @@ step('Wait for fetcher to reconnect', async function () {
   console.log('Reconnect wait period completed');
 });
 
+// Verify the fetcher reconnected soon after the stream was lost
+step('Verify fetcher reconnected within <ms> milliseconds of the stream ending', async function (ms) {
+  const limitMs = parseInt(ms, 10);
+  const streamEndedAt = getStore().get('streamEndedAt');
+  const reconnectedAt = getStore().get('reconnectedAt');
+
+  assert.ok(streamEndedAt, 'Expected the stream to end, but no stream loss was logged');
+  assert.ok(reconnectedAt, 'Expected the fetcher to reconnect, but no reconnection was logged');
+
+  const delayMs = reconnectedAt - streamEndedAt;
+  assert.ok(
+    delayMs <= limitMs,
+    `Expected reconnection within ${limitMs}ms of the stream ending but it took ${delayMs}ms`
+  );
+
+  console.log(`Verified fetcher reconnected ${delayMs}ms after the stream ended`);
+});
+
 // Verify fetcher received additional messages with correct pagination
 step('Verify fetcher received additional messages with correct pagination', async function () {
   const initialCount = getInitialMessageCount();